@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// Something the player asks to do, however the frontend collected it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    Move(i32, i32),
+    Descend,
+    Ascend,
+    PickUp,
+    /// Open the inventory screen, which turns into one of the item actions below
+    Inventory,
+    UseItem(usize),
+    DropItem(usize),
+    Save,
+    Quit,
+    None,
+}
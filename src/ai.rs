@@ -0,0 +1,290 @@
+use std::cell::{OnceCell, RefCell};
+use std::rc::Rc;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::dijkstra::{DijkstraCache, DijkstraMap};
+use crate::enemy::{AiState, Behaviour, Enemy};
+use crate::map::Map;
+use crate::pathfinding::PathScratch;
+
+/// How far away an enemy can spot the player, as a Manhattan distance
+const SIGHT_RANGE: usize = 8;
+/// Chance per turn that a sleeping enemy with the player in view wakes up
+const NOTICE_CHANCE: f64 = 0.25;
+/// Chance that a sleeping enemy within earshot of a noise wakes up
+const WAKE_ON_NOISE_CHANCE: f64 = 0.5;
+/// Chance per turn that a fleeing enemy out of the player's sight recovers 1 HP
+const RECOVER_CHANCE: f64 = 0.2;
+
+/// What an enemy decided to do this turn
+pub enum Intent {
+    Move(usize, usize),
+    Attack,
+    Wait,
+}
+
+/// Everything the enemies on a level share during one round of turns
+pub struct Surroundings<'a> {
+    pub map: &'a Map,
+    pub player: (usize, usize),
+    /// Walking distance to the player, built once per turn
+    pub chase_map: &'a DijkstraMap,
+    /// Flee map, only built the first time an enemy needs it
+    pub flee_map: OnceCell<DijkstraMap>,
+    /// The level's cached maps toward the spawn points wandering enemies are heading for
+    pub wander_maps: &'a DijkstraCache,
+    /// How many steps away the player's last action could be heard
+    pub noise: usize,
+    /// Grids shared by every A* search this turn, so detours don't allocate whole-map grids
+    path_scratch: RefCell<PathScratch>,
+}
+
+impl<'a> Surroundings<'a> {
+    pub fn new(
+        map: &'a Map,
+        player: (usize, usize),
+        chase_map: &'a DijkstraMap,
+        wander_maps: &'a DijkstraCache,
+        noise: usize,
+    ) -> Self {
+        Surroundings {
+            map,
+            player,
+            chase_map,
+            flee_map: OnceCell::new(),
+            wander_maps,
+            noise,
+            path_scratch: RefCell::default(),
+        }
+    }
+
+    /// First step of `enemy`'s A* path to `target`, for when the shared maps give no step
+    fn path_step<F>(&self, enemy: &Enemy, target: (usize, usize), is_occupied: F) -> Option<(usize, usize)>
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        enemy.path_step(target, self.map, &mut self.path_scratch.borrow_mut(), is_occupied)
+    }
+
+    /// Walking distance to a spawn point, shared with every other enemy heading there.
+    /// None for targets that aren't spawn points.
+    fn wander_map(&self, target: (usize, usize)) -> Option<Rc<DijkstraMap>> {
+        self.map.spawn_points.contains(&target).then(|| self.wander_maps.get_or_build(self.map, target))
+    }
+}
+
+/// Update the enemy's state from what it sees and hears, then decide what to do
+pub fn take_turn<R, F>(enemy: &mut Enemy, world: &Surroundings, rng: &mut R, is_occupied: F) -> Intent
+where
+    R: Rng,
+    F: Fn(usize, usize) -> bool,
+{
+    let pos = (enemy.x, enemy.y);
+    let distance = enemy.distance_to(world.player.0, world.player.1);
+    // FOV is symmetric, so the enemy sees the player exactly when the player sees it
+    let sees_player = distance <= SIGHT_RANGE && world.map.is_visible(pos.0, pos.1);
+    let hears_player = world
+        .chase_map
+        .value(pos.0, pos.1)
+        .is_some_and(|steps| (steps as usize) <= world.noise);
+
+    enemy.state = match enemy.state {
+        AiState::Asleep => {
+            let noticed = sees_player && rng.gen_bool(NOTICE_CHANCE);
+            let woken = hears_player && rng.gen_bool(WAKE_ON_NOISE_CHANCE);
+            if noticed || woken {
+                AiState::Hunting { last_known: world.player }
+            } else {
+                AiState::Asleep
+            }
+        }
+        _ if sees_player && enemy.is_badly_hurt() => AiState::Fleeing,
+        AiState::Fleeing if enemy.is_badly_hurt() => AiState::Fleeing,
+        _ if sees_player || hears_player => AiState::Hunting { last_known: world.player },
+        // Recovered enough to stop running; wander off from here
+        AiState::Fleeing => AiState::Wandering { target: pos },
+        state => state,
+    };
+
+    match enemy.state {
+        AiState::Asleep => Intent::Wait,
+        AiState::Fleeing => {
+            if !sees_player {
+                // Out of sight - catch its breath
+                if rng.gen_bool(RECOVER_CHANCE) {
+                    enemy.heal(1);
+                }
+                return Intent::Wait;
+            }
+            let flee_map = world.flee_map.get_or_init(|| world.chase_map.flee(world.map));
+            match flee_map.downhill(pos.0, pos.1, is_occupied) {
+                Some((x, y)) => Intent::Move(x, y),
+                // Cornered - fight back
+                None if distance == 1 => Intent::Attack,
+                None => Intent::Wait,
+            }
+        }
+        AiState::Hunting { .. } if distance == 1 => Intent::Attack,
+        AiState::Hunting { .. } if sees_player => {
+            // Roll downhill toward the player, finding a way around when others block the way
+            world
+                .chase_map
+                .downhill(pos.0, pos.1, &is_occupied)
+                .or_else(|| world.path_step(enemy, world.player, &is_occupied))
+                .map_or(Intent::Wait, |(x, y)| Intent::Move(x, y))
+        }
+        AiState::Hunting { last_known } => {
+            if pos == last_known {
+                // Lost the trail
+                enemy.state = AiState::Wandering { target: pos };
+                return Intent::Wait;
+            }
+            // Heard the player just now, so the chase map already leads to them
+            let step = if last_known == world.player { world.chase_map.downhill(pos.0, pos.1, &is_occupied) } else { None };
+            step.or_else(|| world.path_step(enemy, last_known, &is_occupied))
+                .map_or(Intent::Wait, |(x, y)| Intent::Move(x, y))
+        }
+        AiState::Wandering { .. } if !enemy.enemy_type.has(Behaviour::Wanders) => Intent::Wait,
+        AiState::Wandering { target } => {
+            let step = if pos == target {
+                None
+            } else {
+                // Roll downhill on the shared map for the target, routing around others with A*
+                world
+                    .wander_map(target)
+                    .and_then(|wander_map| wander_map.downhill(pos.0, pos.1, &is_occupied))
+                    .or_else(|| world.path_step(enemy, target, &is_occupied))
+            };
+            if step.is_none() {
+                // Arrived or stuck - pick somewhere else to go
+                if let Some(&next) = world.map.spawn_points.choose(rng) {
+                    enemy.state = AiState::Wandering { target: next };
+                }
+            }
+            step.map_or(Intent::Wait, |(x, y)| Intent::Move(x, y))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::enemy::EnemyType;
+    use crate::game::{FIGHT_NOISE, STEP_NOISE};
+    use crate::map::Tile;
+
+    /// A goblin with 10 HP and the given behaviours
+    fn goblin(x: usize, y: usize, flags: &[Behaviour]) -> Enemy {
+        Enemy::new(x, y, EnemyType::for_tests("goblin", flags))
+    }
+
+    /// `map` as the player standing at `player` sees it
+    fn seen_from(mut map: Map, player: (usize, usize)) -> Map {
+        map.update_fov(player.0, player.1, 8);
+        map
+    }
+
+    /// Let the enemy take one turn with the player at `player` making `noise`
+    fn turn(enemy: &mut Enemy, map: &Map, player: (usize, usize), noise: usize, rng: &mut ChaCha8Rng) -> Intent {
+        let chase_map = DijkstraMap::new(map, &[player]);
+        let wander_maps = DijkstraCache::default();
+        let world = Surroundings::new(map, player, &chase_map, &wander_maps, noise);
+        take_turn(enemy, &world, rng, |x, y| (x, y) == player)
+    }
+
+    /// Whether a sleeping enemy wakes within `turns` turns
+    fn wakes_within(turns: usize, map: &Map, at: (usize, usize), player: (usize, usize), noise: usize) -> bool {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let mut enemy = goblin(at.0, at.1, &[Behaviour::Sleeps]);
+        (0..turns).any(|_| {
+            turn(&mut enemy, map, player, noise, &mut rng);
+            enemy.state != AiState::Asleep
+        })
+    }
+
+    #[test]
+    fn sleepers_wake_when_they_see_the_player() {
+        let map = seen_from(Map::from_rows(&["##########", "#........#", "##########"]), (1, 1));
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let mut enemy = goblin(6, 1, &[Behaviour::Sleeps]);
+        for _ in 0..100 {
+            if enemy.state != AiState::Asleep {
+                break;
+            }
+            turn(&mut enemy, &map, (1, 1), 0, &mut rng);
+        }
+        assert!(enemy.state == AiState::Hunting { last_known: (1, 1) });
+    }
+
+    #[test]
+    fn sleepers_behind_a_door_wake_to_fighting_but_not_footsteps() {
+        let mut map = Map::from_rows(&["##########", "#........#", "##########"]);
+        map.tiles[1][4] = Tile::Door;
+        let map = seen_from(map, (1, 1));
+        assert!(!map.is_visible(7, 1));
+
+        assert!(!wakes_within(100, &map, (7, 1), (1, 1), 0));
+        assert!(!wakes_within(100, &map, (7, 1), (1, 1), STEP_NOISE));
+        assert!(wakes_within(100, &map, (7, 1), (1, 1), FIGHT_NOISE));
+    }
+
+    #[test]
+    fn hunters_head_for_where_the_player_was_then_lose_the_trail_and_wander() {
+        // The player has slipped out of sight around the corner; nothing has been seen yet
+        let mut map = Map::from_rows(&["########", "#......#", "######.#", "######.#", "########"]);
+        map.spawn_points = vec![(1, 1), (6, 3)];
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let mut enemy = goblin(1, 1, &[Behaviour::Wanders]);
+        enemy.state = AiState::Hunting { last_known: (4, 1) };
+
+        for x in 2..=4 {
+            assert!(matches!(turn(&mut enemy, &map, (6, 3), 0, &mut rng), Intent::Move(nx, 1) if nx == x));
+            enemy.x = x;
+        }
+        assert!(matches!(turn(&mut enemy, &map, (6, 3), 0, &mut rng), Intent::Wait));
+        assert!(enemy.state == AiState::Wandering { target: (4, 1) }, "lost the trail");
+
+        // Standing on its target, it picks a spawn point to wander to next
+        turn(&mut enemy, &map, (6, 3), 0, &mut rng);
+        assert!(matches!(enemy.state, AiState::Wandering { target } if map.spawn_points.contains(&target)));
+    }
+
+    #[test]
+    fn badly_hurt_enemies_flee_then_hunt_again_once_recovered() {
+        let map = seen_from(Map::from_rows(&["##########", "#........#", "##########"]), (1, 1));
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let mut enemy = goblin(4, 1, &[Behaviour::Flees]);
+        enemy.state = AiState::Hunting { last_known: (1, 1) };
+        enemy.hp = 2;
+
+        assert!(matches!(turn(&mut enemy, &map, (1, 1), 0, &mut rng), Intent::Move(5, 1)));
+        assert!(enemy.state == AiState::Fleeing);
+
+        enemy.heal(10);
+        turn(&mut enemy, &map, (1, 1), 0, &mut rng);
+        assert!(enemy.state == AiState::Hunting { last_known: (1, 1) });
+    }
+
+    #[test]
+    fn wanderers_pick_a_new_target_on_arrival() {
+        // With no field of view worked out, the enemy can't see the player
+        let mut map = Map::from_rows(&["##########", "#........#", "##########"]);
+        map.spawn_points = vec![(1, 1), (8, 1)];
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let mut enemy = goblin(8, 1, &[Behaviour::Wanders]);
+        enemy.state = AiState::Wandering { target: (8, 1) };
+
+        for _ in 0..20 {
+            turn(&mut enemy, &map, (1, 1), 0, &mut rng);
+            if enemy.state != (AiState::Wandering { target: (8, 1) }) {
+                break;
+            }
+        }
+        assert!(enemy.state == AiState::Wandering { target: (1, 1) });
+    }
+}
@@ -0,0 +1,80 @@
+/// The map cell shown in the top-left corner of the screen. It only scrolls
+/// when the player comes within the margin of an edge of the view.
+#[derive(Default)]
+pub struct Camera {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Camera {
+    /// Scroll to keep `target` inside the view, but never past the edges of the map
+    pub fn follow(&mut self, target: (usize, usize), view: (usize, usize), map: (usize, usize), margin: usize) {
+        self.x = follow_axis(self.x, target.0, view.0, map.0, margin);
+        self.y = follow_axis(self.y, target.1, view.1, map.1, margin);
+    }
+}
+
+fn follow_axis(offset: usize, target: usize, view: usize, size: usize, margin: usize) -> usize {
+    if view >= size {
+        return 0;
+    }
+    // More than half the view would leave nowhere for the target to stand
+    let margin = margin.min(view.saturating_sub(1) / 2);
+    let offset = if target < offset + margin {
+        target.saturating_sub(margin)
+    } else if target + margin >= offset + view {
+        target + margin + 1 - view
+    } else {
+        offset
+    };
+    offset.min(size - view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stays_put_while_the_target_is_clear_of_the_margins() {
+        assert_eq!(follow_axis(10, 30, 40, 100, 6), 10);
+        assert_eq!(follow_axis(10, 16, 40, 100, 6), 10);
+        assert_eq!(follow_axis(10, 43, 40, 100, 6), 10);
+    }
+
+    #[test]
+    fn scrolls_when_the_target_enters_a_margin() {
+        assert_eq!(follow_axis(10, 15, 40, 100, 6), 9);
+        assert_eq!(follow_axis(10, 44, 40, 100, 6), 11);
+        // A jump (such as taking the stairs) brings the view straight to the target
+        assert_eq!(follow_axis(0, 80, 40, 100, 6), 47);
+    }
+
+    #[test]
+    fn never_scrolls_past_the_edges_of_the_map() {
+        assert_eq!(follow_axis(10, 2, 40, 100, 6), 0);
+        assert_eq!(follow_axis(50, 98, 40, 100, 6), 60);
+        assert_eq!(follow_axis(60, 99, 40, 100, 6), 60);
+    }
+
+    #[test]
+    fn maps_smaller_than_the_view_are_not_scrolled() {
+        assert_eq!(follow_axis(0, 19, 40, 20, 6), 0);
+        assert_eq!(follow_axis(5, 19, 20, 20, 6), 0);
+    }
+
+    #[test]
+    fn margins_wider_than_half_the_view_are_narrowed() {
+        // A 5-wide view keeps 2 tiles either side of the target
+        assert_eq!(follow_axis(0, 3, 5, 100, 6), 1);
+        assert_eq!(follow_axis(10, 11, 5, 100, 6), 9);
+    }
+
+    #[test]
+    fn follows_on_both_axes() {
+        let mut camera = Camera::default();
+        camera.follow((70, 30), (40, 20), (100, 35), 6);
+        assert_eq!((camera.x, camera.y), (37, 15));
+        camera.follow((71, 30), (40, 20), (100, 35), 6);
+        assert_eq!((camera.x, camera.y), (38, 15));
+    }
+}
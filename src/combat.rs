@@ -1,40 +1,189 @@
 use rand::Rng;
+use crate::grammar::{capitalize, Noun};
 use crate::player::Player;
 use crate::enemy::Enemy;
 
+/// Chance to hit, in percent, when accuracy and evasion are equal
+const BASE_HIT_CHANCE: i32 = 75;
+/// Every attack has at least this chance to hit and to miss, in percent
+const MIN_HIT_CHANCE: i32 = 5;
+const MAX_HIT_CHANCE: i32 = 95;
+const CRIT_CHANCE: f64 = 0.05;
+/// Critical hits multiply damage before armor is subtracted
+const CRIT_MULTIPLIER: i32 = 2;
+
+/// The numbers a fighter brings to an exchange of blows.
+/// Accuracy and evasion are percentage points added to or taken off the hit chance.
+#[derive(Clone, Copy)]
+pub struct CombatStats {
+    pub power: i32,
+    pub accuracy: i32,
+    pub evasion: i32,
+    pub armor: i32,
+}
+
+/// One side of a fight
+#[derive(Clone, PartialEq)]
+pub enum Combatant {
+    Player,
+    Monster(Noun),
+}
+
+/// How a single attack landed
+#[derive(Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Miss,
+    Hit,
+    Critical,
+}
+
 pub struct CombatResult {
+    pub attacker: Combatant,
+    pub defender: Combatant,
+    pub outcome: Outcome,
     pub damage: i32,
+    pub killed: bool,
     pub message: String,
 }
 
-pub fn player_attack(player: &Player, enemy: &mut Enemy) -> CombatResult {
-    let mut rng = rand::thread_rng();
-    let variance = rng.gen_range(0..=3);
-    let damage = (player.power - variance).max(1);
+/// Roll to hit and for a critical, then work out the damage after armor.
+/// Damage before armor is the attacker's power less up to `variance`.
+fn resolve_attack<R: Rng>(rng: &mut R, attacker: CombatStats, defender: CombatStats, variance: i32) -> (Outcome, i32) {
+    if rng.gen_range(0..100) >= hit_chance(attacker, defender) {
+        return (Outcome::Miss, 0);
+    }
+
+    let damage = attacker.power - rng.gen_range(0..=variance);
+    let outcome = if rng.gen_bool(CRIT_CHANCE) { Outcome::Critical } else { Outcome::Hit };
+    (outcome, damage_through_armor(damage, outcome, defender.armor))
+}
+
+/// Chance in percent that a blow from `attacker` lands on `defender`
+fn hit_chance(attacker: CombatStats, defender: CombatStats) -> i32 {
+    (BASE_HIT_CHANCE + attacker.accuracy - defender.evasion).clamp(MIN_HIT_CHANCE, MAX_HIT_CHANCE)
+}
+
+/// Damage a landed blow deals through `armor`, multiplied first if it was critical
+fn damage_through_armor(damage: i32, outcome: Outcome, armor: i32) -> i32 {
+    let damage = if outcome == Outcome::Critical { damage * CRIT_MULTIPLIER } else { damage };
+    // A blow that lands always hurts a little, however thick the armor
+    (damage - armor).max(1)
+}
+
+pub fn player_attack<R: Rng>(rng: &mut R, player: &Player, enemy: &mut Enemy) -> CombatResult {
+    let (outcome, damage) = resolve_attack(rng, player.combat_stats(), enemy.combat_stats(), 3);
 
     enemy.take_damage(damage);
 
-    let message = if enemy.is_alive() {
-        format!("You hit the goblin for {} damage!", damage)
-    } else {
-        format!("You killed the goblin!")
+    let target = enemy.enemy_type.noun.definite();
+    let message = match outcome {
+        Outcome::Miss => format!("You miss {}.", target),
+        _ if !enemy.is_alive() => format!("{}You killed {}!", crit_prefix(outcome), target),
+        _ => format!("{}You hit {} for {} damage!", crit_prefix(outcome), target, damage),
     };
 
-    CombatResult { damage, message }
+    CombatResult {
+        attacker: Combatant::Player,
+        defender: Combatant::Monster(enemy.enemy_type.noun.clone()),
+        outcome,
+        damage,
+        killed: !enemy.is_alive(),
+        message,
+    }
 }
 
-pub fn enemy_attack(enemy: &Enemy, player: &mut Player) -> CombatResult {
-    let mut rng = rand::thread_rng();
-    let variance = rng.gen_range(0..=2);
-    let damage = (enemy.power - variance).max(1);
+pub fn enemy_attack<R: Rng>(rng: &mut R, enemy: &Enemy, player: &mut Player) -> CombatResult {
+    let (outcome, damage) = resolve_attack(rng, enemy.combat_stats(), player.combat_stats(), 2);
 
     player.take_damage(damage);
 
-    let message = if player.is_alive() {
-        format!("The goblin hits you for {} damage!", damage)
-    } else {
-        format!("The goblin killed you!")
+    let attacker = capitalize(&enemy.enemy_type.noun.definite());
+    let message = match outcome {
+        Outcome::Miss => format!("{} misses you.", attacker),
+        _ if !player.is_alive() => format!("{}{} killed you!", crit_prefix(outcome), attacker),
+        _ => format!("{}{} hits you for {} damage!", crit_prefix(outcome), attacker, damage),
     };
 
-    CombatResult { damage, message }
+    CombatResult {
+        attacker: Combatant::Monster(enemy.enemy_type.noun.clone()),
+        defender: Combatant::Player,
+        outcome,
+        damage,
+        killed: !player.is_alive(),
+        message,
+    }
+}
+
+fn crit_prefix(outcome: Outcome) -> &'static str {
+    if outcome == Outcome::Critical {
+        "Critical hit! "
+    } else {
+        ""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn stats(power: i32, accuracy: i32, evasion: i32, armor: i32) -> CombatStats {
+        CombatStats { power, accuracy, evasion, armor }
+    }
+
+    #[test]
+    fn the_hit_chance_stays_between_5_and_95_percent() {
+        let plain = stats(5, 0, 0, 0);
+        assert_eq!(hit_chance(plain, plain), BASE_HIT_CHANCE);
+        assert_eq!(hit_chance(stats(5, 10, 0, 0), stats(5, 0, 25, 0)), 60);
+        assert_eq!(hit_chance(stats(5, 500, 0, 0), plain), MAX_HIT_CHANCE);
+        assert_eq!(hit_chance(plain, stats(5, 0, 500, 0)), MIN_HIT_CHANCE);
+    }
+
+    #[test]
+    fn armor_never_stops_a_landed_blow_entirely() {
+        assert_eq!(damage_through_armor(5, Outcome::Hit, 2), 3);
+        assert_eq!(damage_through_armor(3, Outcome::Hit, 10), 1);
+        assert_eq!(damage_through_armor(0, Outcome::Critical, 10), 1);
+    }
+
+    #[test]
+    fn criticals_double_damage_before_armor() {
+        assert_eq!(damage_through_armor(5, Outcome::Critical, 2), 8);
+        assert_eq!(damage_through_armor(5, Outcome::Critical, 0), 10);
+    }
+
+    #[test]
+    fn rolls_follow_the_clamped_chances() {
+        let mut rng = ChaCha8Rng::seed_from_u64(13);
+        let (hopeless, untouchable) = (stats(4, -500, 0, 0), stats(4, 0, 0, 20));
+        let sure = stats(4, 500, 0, 0);
+
+        let attacks = 10_000;
+        let mut landed = 0;
+        let mut misses = 0;
+        let mut crits = 0;
+        for _ in 0..attacks {
+            if resolve_attack(&mut rng, hopeless, untouchable, 2).0 != Outcome::Miss {
+                landed += 1;
+            }
+            let (outcome, damage) = resolve_attack(&mut rng, sure, untouchable, 2);
+            match outcome {
+                Outcome::Miss => misses += 1,
+                Outcome::Critical => crits += 1,
+                Outcome::Hit => {}
+            }
+            if outcome != Outcome::Miss {
+                assert_eq!(damage, 1, "armor takes every hit down to 1");
+            }
+        }
+
+        // 5% of 10,000 attacks is 500; allow for the luck of the draw
+        for count in [landed, misses] {
+            assert!((400..600).contains(&count), "{} of {} attacks", count, attacks);
+        }
+        assert!(crits > 0);
+    }
 }
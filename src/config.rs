@@ -0,0 +1,173 @@
+use std::env;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::glyph::Charset;
+use crate::map::Generator;
+
+pub const USAGE: &str = "Usage: worldfall [--seed <u64>] [--generator <rooms|bsp|caves>] [--loops <ratio>] [--fov-radius <n>] [--map-size <WxH>] [--monsters <file>] [--theme <dark|light|high-contrast|file>] [--glyphs <unicode|ascii|auto>] [--camera-margin <n>] [--continue] [--replay <file> [--replay-delay <ms>]]";
+
+/// Extra corridors per spanning-tree corridor when none is given
+const DEFAULT_LOOP_RATIO: f64 = 0.25;
+const DEFAULT_FOV_RADIUS: usize = 8;
+const DEFAULT_MAP_WIDTH: usize = 100;
+const DEFAULT_MAP_HEIGHT: usize = 35;
+/// Smallest map the room generators can still fit rooms into
+const MIN_MAP_WIDTH: usize = 20;
+const MIN_MAP_HEIGHT: usize = 15;
+/// Largest map allowed, keeping each level's tile grids and distance maps to a few megabytes
+const MAX_MAP_WIDTH: usize = 400;
+const MAX_MAP_HEIGHT: usize = 200;
+/// How close the player gets to the edge of the screen before the map scrolls
+const DEFAULT_CAMERA_MARGIN: usize = 6;
+/// Pause between replayed actions when none is given
+const DEFAULT_REPLAY_DELAY_MS: u64 = 100;
+const DEFAULT_THEME: &str = "dark";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub seed: u64,
+    pub generator: Generator,
+    pub loop_ratio: f64,
+    pub fov_radius: usize,
+    pub map_width: usize,
+    pub map_height: usize,
+    /// Monster roster file; the default data file is used when unset
+    pub monsters: Option<String>,
+    /// Built-in theme name or theme file; a display preference, so not saved with the game
+    #[serde(skip)]
+    pub theme: String,
+    /// Characters the map is drawn with; picked from the locale unless given
+    #[serde(skip)]
+    pub charset: Charset,
+    #[serde(skip)]
+    pub camera_margin: usize,
+    /// Resume the saved game instead of starting a new one
+    #[serde(skip)]
+    pub continue_game: bool,
+    /// Replay file to play back instead of playing
+    #[serde(skip)]
+    pub replay: Option<String>,
+    #[serde(skip)]
+    pub replay_delay: Duration,
+}
+
+impl Config {
+    /// Default settings for a run with the given seed, as if no options were passed
+    pub fn with_seed(seed: u64) -> Self {
+        Config {
+            seed,
+            generator: Generator::Rooms,
+            loop_ratio: DEFAULT_LOOP_RATIO,
+            fov_radius: DEFAULT_FOV_RADIUS,
+            map_width: DEFAULT_MAP_WIDTH,
+            map_height: DEFAULT_MAP_HEIGHT,
+            monsters: None,
+            theme: DEFAULT_THEME.to_string(),
+            charset: Charset::detect(),
+            camera_margin: DEFAULT_CAMERA_MARGIN,
+            continue_game: false,
+            replay: None,
+            replay_delay: Duration::from_millis(DEFAULT_REPLAY_DELAY_MS),
+        }
+    }
+
+    /// Parse command-line options, picking a random seed if none was given
+    pub fn from_args() -> Result<Self, String> {
+        let mut config = Config::with_seed(rand::random());
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a value")?;
+                    let parsed = value
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid seed: {}", value))?;
+                    config.seed = parsed;
+                }
+                "--generator" => {
+                    let value = args.next().ok_or("--generator needs a value")?;
+                    config.generator = Generator::from_name(&value)
+                        .ok_or_else(|| format!("unknown generator: {}", value))?;
+                }
+                "--loops" => {
+                    let value = args.next().ok_or("--loops needs a value")?;
+                    config.loop_ratio = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|ratio| *ratio >= 0.0)
+                        .ok_or_else(|| format!("invalid loop ratio: {}", value))?;
+                }
+                "--fov-radius" => {
+                    let value = args.next().ok_or("--fov-radius needs a value")?;
+                    config.fov_radius = value
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid FOV radius: {}", value))?;
+                }
+                "--map-size" => {
+                    let value = args.next().ok_or("--map-size needs a value")?;
+                    (config.map_width, config.map_height) = parse_map_size(&value)
+                        .ok_or_else(|| format!("invalid map size: {} (from {}x{} to {}x{})", value, MIN_MAP_WIDTH, MIN_MAP_HEIGHT, MAX_MAP_WIDTH, MAX_MAP_HEIGHT))?;
+                }
+                "--camera-margin" => {
+                    let value = args.next().ok_or("--camera-margin needs a value")?;
+                    config.camera_margin = value
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid camera margin: {}", value))?;
+                }
+                "--monsters" => {
+                    config.monsters = Some(args.next().ok_or("--monsters needs a value")?);
+                }
+                "--theme" => {
+                    config.theme = args.next().ok_or("--theme needs a value")?;
+                }
+                "--glyphs" => {
+                    let value = args.next().ok_or("--glyphs needs a value")?;
+                    config.charset = Charset::from_name(&value).ok_or_else(|| format!("unknown glyph set: {}", value))?;
+                }
+                "--continue" => config.continue_game = true,
+                "--replay" => {
+                    config.replay = Some(args.next().ok_or("--replay needs a value")?);
+                }
+                "--replay-delay" => {
+                    let value = args.next().ok_or("--replay-delay needs a value")?;
+                    let ms = value
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid replay delay: {}", value))?;
+                    config.replay_delay = Duration::from_millis(ms);
+                }
+                _ => return Err(format!("unknown option: {}", arg)),
+            }
+        }
+
+        // Nothing is further away than the map is wide or tall, and a bigger radius only overflows
+        config.fov_radius = config.fov_radius.min(config.map_width.max(config.map_height));
+        Ok(config)
+    }
+}
+
+/// Read a map size written as "<width>x<height>"
+fn parse_map_size(value: &str) -> Option<(usize, usize)> {
+    let (width, height) = value.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    let allowed = (MIN_MAP_WIDTH..=MAX_MAP_WIDTH).contains(&width) && (MIN_MAP_HEIGHT..=MAX_MAP_HEIGHT).contains(&height);
+    allowed.then_some((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_sizes_must_be_within_bounds() {
+        assert_eq!(parse_map_size("100x35"), Some((100, 35)));
+        assert_eq!(parse_map_size("20x15"), Some((MIN_MAP_WIDTH, MIN_MAP_HEIGHT)));
+        assert_eq!(parse_map_size("400x200"), Some((MAX_MAP_WIDTH, MAX_MAP_HEIGHT)));
+        assert_eq!(parse_map_size("19x15"), None);
+        assert_eq!(parse_map_size("401x35"), None);
+        assert_eq!(parse_map_size("100000x100000"), None);
+        assert_eq!(parse_map_size("100by35"), None);
+    }
+}
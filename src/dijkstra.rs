@@ -0,0 +1,202 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::rc::Rc;
+
+use crate::map::Map;
+
+/// Value of tiles no goal can reach
+const UNREACHABLE: i32 = i32::MAX;
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+/// Walking distance from the nearest goal to every tile of a map. Built once per
+/// turn and shared: any number of creatures can head for a goal by rolling
+/// downhill, or away from it on the inverted "flee" map.
+pub struct DijkstraMap {
+    width: usize,
+    height: usize,
+    values: Vec<i32>,
+}
+
+impl DijkstraMap {
+    /// Distance map toward the closest of `goals`
+    pub fn new(map: &Map, goals: &[(usize, usize)]) -> Self {
+        let seeds = goals
+            .iter()
+            .filter(|&&(x, y)| map.is_walkable(x, y))
+            .map(|&pos| (pos, 0))
+            .collect();
+        Self::from_seeds(map, seeds)
+    }
+
+    /// Map for running away from this map's goals. Values are negated and scaled
+    /// by 1.2 before being relaxed again, so going downhill leads away from the
+    /// goals but prefers escape routes over backing into the nearest corner.
+    pub fn flee(&self, map: &Map) -> Self {
+        let seeds = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let value = self.values[y * self.width + x];
+                (value != UNREACHABLE).then(|| ((x, y), -(value * 6 / 5)))
+            })
+            .collect();
+        Self::from_seeds(map, seeds)
+    }
+
+    /// Relax every walkable tile outward from seeded starting values
+    fn from_seeds(map: &Map, seeds: Vec<((usize, usize), i32)>) -> Self {
+        let mut dijkstra = DijkstraMap {
+            width: map.width,
+            height: map.height,
+            values: vec![UNREACHABLE; map.width * map.height],
+        };
+
+        let mut open = BinaryHeap::new();
+        for ((x, y), value) in seeds {
+            let index = y * dijkstra.width + x;
+            if value < dijkstra.values[index] {
+                dijkstra.values[index] = value;
+                open.push(Reverse((value, (x, y))));
+            }
+        }
+
+        while let Some(Reverse((value, (x, y)))) = open.pop() {
+            if value > dijkstra.values[y * dijkstra.width + x] {
+                continue;
+            }
+            for (dx, dy) in DIRECTIONS {
+                let nx = (x as i32 + dx) as usize;
+                let ny = (y as i32 + dy) as usize;
+                if !map.is_walkable(nx, ny) {
+                    continue;
+                }
+                let index = ny * dijkstra.width + nx;
+                if value + 1 < dijkstra.values[index] {
+                    dijkstra.values[index] = value + 1;
+                    open.push(Reverse((value + 1, (nx, ny))));
+                }
+            }
+        }
+
+        dijkstra
+    }
+
+    pub fn value(&self, x: usize, y: usize) -> Option<i32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let value = self.values[y * self.width + x];
+        (value != UNREACHABLE).then_some(value)
+    }
+
+    /// The neighbouring tile with the lowest value below the current one, skipping
+    /// tiles `is_blocked` rejects. None when there is nowhere better to go.
+    pub fn downhill<F>(&self, x: usize, y: usize, is_blocked: F) -> Option<(usize, usize)>
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        let mut best = self.value(x, y)?;
+        let mut step = None;
+        for (dx, dy) in DIRECTIONS {
+            let nx = (x as i32 + dx) as usize;
+            let ny = (y as i32 + dy) as usize;
+            if let Some(value) = self.value(nx, ny) {
+                if value < best && !is_blocked(nx, ny) {
+                    best = value;
+                    step = Some((nx, ny));
+                }
+            }
+        }
+        step
+    }
+}
+
+/// Maps toward the goals creatures are heading for, so everyone heading for the same
+/// spot shares one map. Owners drop goals nobody heads for any more with `retain`.
+#[derive(Default)]
+pub struct DijkstraCache {
+    maps: RefCell<HashMap<(usize, usize), Rc<DijkstraMap>>>,
+}
+
+impl DijkstraCache {
+    /// The map toward `goal`, built now unless it is already cached
+    pub fn get_or_build(&self, map: &Map, goal: (usize, usize)) -> Rc<DijkstraMap> {
+        let mut maps = self.maps.borrow_mut();
+        Rc::clone(maps.entry(goal).or_insert_with(|| Rc::new(DijkstraMap::new(map, &[goal]))))
+    }
+
+    /// Forget the maps toward every goal not in `goals`
+    pub fn retain(&mut self, goals: &[(usize, usize)]) {
+        self.maps.get_mut().retain(|goal, _| goals.contains(goal));
+    }
+
+    pub fn clear(&mut self) {
+        self.maps.get_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn several_goals_give_the_distance_to_the_nearest() {
+        let map = Map::from_rows(&["###########", "#.........#", "###########"]);
+        let dijkstra = DijkstraMap::new(&map, &[(1, 1), (9, 1)]);
+        assert_eq!(dijkstra.value(1, 1), Some(0));
+        assert_eq!(dijkstra.value(3, 1), Some(2));
+        assert_eq!(dijkstra.value(5, 1), Some(4));
+        assert_eq!(dijkstra.value(8, 1), Some(1));
+        assert_eq!(dijkstra.value(0, 1), None, "walls are unreachable");
+    }
+
+    #[test]
+    fn fleeing_takes_the_escape_route_rather_than_a_dead_end() {
+        // Both first steps put one more tile between the enemy and the player, but
+        // only the corridor leads anywhere
+        let map = Map::from_rows(&[
+            "############",
+            "#P.E.......#",
+            "###.########",
+            "###.########",
+            "############",
+        ]);
+        let chase = DijkstraMap::new(&map, &[(1, 1)]);
+        let flee = chase.flee(&map);
+
+        let step = flee.downhill(3, 1, |_, _| false).unwrap();
+        assert_eq!(step, (4, 1));
+        assert!(chase.value(step.0, step.1) > chase.value(3, 1));
+    }
+
+    #[test]
+    fn downhill_skips_blocked_tiles() {
+        let map = Map::from_rows(&["######", "#....#", "#....#", "######"]);
+        let dijkstra = DijkstraMap::new(&map, &[(1, 1)]);
+        assert_eq!(dijkstra.downhill(3, 2, |_, _| false), Some((3, 1)));
+        assert_eq!(dijkstra.downhill(3, 2, |x, y| (x, y) == (3, 1)), Some((2, 2)));
+        assert_eq!(dijkstra.downhill(3, 2, |x, y| (x, y) == (3, 1) || (x, y) == (2, 2)), None);
+        assert_eq!(dijkstra.downhill(1, 1, |_, _| false), None, "nowhere lower than the goal");
+    }
+
+    #[test]
+    fn the_cache_shares_maps_and_forgets_goals_no_longer_wanted() {
+        let map = Map::from_rows(&["######", "#....#", "######"]);
+        let mut cache = DijkstraCache::default();
+
+        let first = cache.get_or_build(&map, (1, 1));
+        assert!(Rc::ptr_eq(&first, &cache.get_or_build(&map, (1, 1))));
+        let second = cache.get_or_build(&map, (4, 1));
+        assert_eq!(second.value(1, 1), Some(3));
+
+        cache.retain(&[(1, 1)]);
+        assert!(Rc::ptr_eq(&first, &cache.get_or_build(&map, (1, 1))), "wanted goals are kept");
+        assert!(!Rc::ptr_eq(&second, &cache.get_or_build(&map, (4, 1))), "dropped goals are rebuilt");
+
+        cache.clear();
+        assert!(cache.maps.borrow().is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::dijkstra::DijkstraCache;
+use crate::enemy::{AiState, Behaviour, Enemy, EnemyType};
+use crate::item::{FloorItem, Item};
+use crate::map::Map;
+use crate::roster::Roster;
+
+/// Share of sleepy enemies that start out asleep; the rest start wandering
+const ASLEEP_AT_SPAWN: f64 = 0.5;
+
+/// One floor of the dungeon: its map (including fog of war) and everything living on it
+#[derive(Serialize, Deserialize)]
+pub struct Level {
+    pub map: Map,
+    pub enemies: Vec<Enemy>,
+    pub items: Vec<FloorItem>,
+    /// Walking distance to the spawn points wandering enemies are heading for, each built
+    /// when the first of them sets off. Opening doors doesn't change where can be walked,
+    /// so they never go stale.
+    #[serde(skip)]
+    pub wander_maps: DijkstraCache,
+}
+
+impl Level {
+    /// Populate a freshly generated map with monsters from the roster at its spawn points.
+    /// `uniques` lists the unique monsters already in the dungeon and gains any spawned here.
+    pub fn new<R: Rng>(map: Map, rng: &mut R, roster: &Roster, depth: usize, uniques: &mut Vec<String>) -> Self {
+        let mut enemies = Vec::new();
+        for (x, y) in map.enemy_spawn_points() {
+            if let Some(enemy_type) = roster.pick(rng, depth, uniques) {
+                enemies.push(spawn_enemy(rng, x, y, enemy_type, uniques));
+            }
+        }
+        let items = map
+            .item_spawns
+            .iter()
+            .map(|&(x, y)| FloorItem { x, y, item: Item::random(rng, depth) })
+            .collect();
+        Level { map, enemies, items, wander_maps: DijkstraCache::default() }
+    }
+
+    /// Make sure the level has at least one monster, placing it as far from the player's
+    /// start as possible; used on the deepest level, where clearing the level wins the game
+    pub fn add_guardian<R: Rng>(&mut self, rng: &mut R, roster: &Roster, depth: usize, uniques: &mut Vec<String>) {
+        if !self.enemies.is_empty() {
+            return;
+        }
+        let Some((x, y)) = self.map.farthest_floor() else {
+            return;
+        };
+        if let Some(enemy_type) = roster.pick_or_deepest(rng, depth, uniques) {
+            self.enemies.push(spawn_enemy(rng, x, y, enemy_type, uniques));
+        }
+    }
+
+    /// Drop the wander maps no living wanderer is heading for any more, so there are
+    /// never more of them than there are wanderers
+    pub fn forget_unused_wander_maps(&mut self) {
+        let targets: Vec<(usize, usize)> = self
+            .enemies
+            .iter()
+            .filter(|e| e.is_alive() && e.enemy_type.has(Behaviour::Wanders))
+            .filter_map(|e| match e.state {
+                AiState::Wandering { target } => Some(target),
+                _ => None,
+            })
+            .collect();
+        self.wander_maps.retain(&targets);
+    }
+
+    /// Grid of tiles currently taken by a living enemy
+    pub fn occupancy(&self) -> Vec<Vec<bool>> {
+        let mut occupied = vec![vec![false; self.map.width]; self.map.height];
+        for enemy in self.enemies.iter().filter(|e| e.is_alive()) {
+            occupied[enemy.y][enemy.x] = true;
+        }
+        occupied
+    }
+
+    /// The item on top of the pile at a position, if there is one
+    pub fn item_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.items.iter().rposition(|i| i.x == x && i.y == y)
+    }
+
+    /// Update where the player last saw each enemy after the field of view changes.
+    /// A sighting is forgotten once its spot is back in view with nobody there.
+    pub fn remember_enemies(&mut self) {
+        for enemy in self.enemies.iter_mut().filter(|e| e.is_alive()) {
+            if self.map.is_visible(enemy.x, enemy.y) {
+                enemy.last_seen = Some((enemy.x, enemy.y));
+            } else if enemy.last_seen.is_some_and(|(x, y)| self.map.is_visible(x, y)) {
+                enemy.last_seen = None;
+            }
+        }
+    }
+}
+
+/// A new enemy of the given type, asleep or wandering, recorded in `uniques` if it is unique
+fn spawn_enemy<R: Rng>(rng: &mut R, x: usize, y: usize, enemy_type: &EnemyType, uniques: &mut Vec<String>) -> Enemy {
+    if enemy_type.noun.unique {
+        uniques.push(enemy_type.noun.name.clone());
+    }
+
+    let mut enemy = Enemy::new(x, y, enemy_type.clone());
+    if !enemy.enemy_type.has(Behaviour::Sleeps) || !rng.gen_bool(ASLEEP_AT_SPAWN) {
+        enemy.state = AiState::Wandering { target: (x, y) };
+    }
+    enemy
+}
+
+/// Every level generated so far, kept exactly as the player left it
+#[derive(Serialize, Deserialize)]
+pub struct Dungeon {
+    levels: Vec<Level>,
+    current: usize,
+}
+
+impl Dungeon {
+    pub fn new(first: Level) -> Self {
+        Dungeon {
+            levels: vec![first],
+            current: 0,
+        }
+    }
+
+    /// Current depth, starting at 1 for the top level
+    pub fn depth(&self) -> usize {
+        self.current + 1
+    }
+
+    pub fn current(&self) -> &Level {
+        &self.levels[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Level {
+        &mut self.levels[self.current]
+    }
+
+    pub fn has_level(&self, depth: usize) -> bool {
+        depth >= 1 && depth <= self.levels.len()
+    }
+
+    /// Store the next level down; levels are always generated in order
+    pub fn add_level(&mut self, level: Level) {
+        self.levels.push(level);
+    }
+
+    /// Names of every unique monster spawned so far, dead or alive
+    pub fn unique_names(&self) -> Vec<String> {
+        self.levels
+            .iter()
+            .flat_map(|level| &level.enemies)
+            .filter(|e| e.enemy_type.noun.unique)
+            .map(|e| e.enemy_type.noun.name.clone())
+            .collect()
+    }
+
+    pub fn set_depth(&mut self, depth: usize) {
+        if self.has_level(depth) {
+            self.current = depth - 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::Level;
+    use crate::map::{Map, Room, Tile};
+    use crate::roster::Roster;
+
+    const SHALLOW_ROSTER: &str = r#"
+        [[monster]]
+        name = "kobold"
+        glyph = "k"
+        hp = 4
+        power = 2
+        min_depth = 1
+        max_depth = 2
+        weight = 1
+    "#;
+
+    /// A single room with the player's start in it and nowhere for monsters to spawn
+    fn one_room_map() -> Map {
+        let mut map = Map::new(20, 10);
+        let room = Room::new(2, 2, 12, 5);
+        for row in &mut map.tiles[2..7] {
+            row[2..14].fill(Tile::Floor);
+        }
+        map.spawn_points = vec![room.center()];
+        map.rooms.push(room);
+        map.room_links.push(Vec::new());
+        map
+    }
+
+    #[test]
+    fn a_guardian_is_added_to_a_level_with_no_monsters() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let roster = Roster::parse(SHALLOW_ROSTER).unwrap();
+        let mut level = Level::new(one_room_map(), &mut rng, &roster, 5, &mut Vec::new());
+        assert!(level.enemies.is_empty());
+
+        level.add_guardian(&mut rng, &roster, 5, &mut Vec::new());
+        assert_eq!(level.enemies.len(), 1);
+        let guardian = &level.enemies[0];
+        assert_eq!(guardian.enemy_type.noun.name, "kobold");
+        assert_ne!((guardian.x, guardian.y), level.map.player_spawn());
+        assert!(level.map.is_walkable(guardian.x, guardian.y));
+    }
+}
@@ -1,56 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+use crate::combat::CombatStats;
+use crate::glyph::{Colour, Glyph};
+use crate::grammar::Noun;
 use crate::map::Map;
+use crate::pathfinding::{find_path, PathScratch};
+
+/// Energy an enemy spends per action; speed is how much it gains each turn
+const ENERGY_PER_ACTION: u32 = 100;
+
+/// Behaviour flags a monster definition can carry
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Behaviour {
+    /// May start out asleep
+    Sleeps,
+    /// Roams the level when it has nothing better to do
+    Wanders,
+    /// Runs away when badly hurt
+    Flees,
+}
 
-#[derive(Clone, Copy, PartialEq)]
-pub enum EnemyType {
-    Goblin,
+/// A kind of monster, as defined in the roster data file
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EnemyType {
+    #[serde(flatten)]
+    pub noun: Noun,
+    pub glyph: char,
+    /// Palette colour for the glyph; roster files that leave it out get white
+    #[serde(default = "default_colour")]
+    pub colour: Colour,
+    pub hp: i32,
+    pub power: i32,
+    /// Percentage points added to this monster's chance to hit
+    #[serde(default)]
+    pub accuracy: i32,
+    /// Percentage points taken off the chance to hit this monster
+    #[serde(default)]
+    pub evasion: i32,
+    /// Subtracted from the damage of every blow that lands
+    #[serde(default)]
+    pub armor: i32,
+    #[serde(default = "default_speed")]
+    pub speed: u32,
+    #[serde(default)]
+    pub flags: Vec<Behaviour>,
+    pub min_depth: usize,
+    pub max_depth: usize,
+    pub weight: u32,
+    /// Experience awarded to the player for the kill
+    #[serde(default)]
+    pub xp: u32,
+}
+
+fn default_speed() -> u32 {
+    ENERGY_PER_ACTION
+}
+
+fn default_colour() -> Colour {
+    Colour::White
 }
 
 impl EnemyType {
-    pub fn to_char(&self) -> char {
-        match self {
-            EnemyType::Goblin => 'g',
-        }
+    pub fn to_glyph(&self) -> Glyph {
+        Glyph::new(self.glyph, self.colour)
     }
 
-    pub fn base_hp(&self) -> i32 {
-        match self {
-            EnemyType::Goblin => 6,
-        }
+    pub fn has(&self, flag: Behaviour) -> bool {
+        self.flags.contains(&flag)
     }
 
-    pub fn base_power(&self) -> i32 {
-        match self {
-            EnemyType::Goblin => 3,
+    /// A plain 10 HP monster found at every depth, for tests
+    #[cfg(test)]
+    pub(crate) fn for_tests(name: &str, flags: &[Behaviour]) -> Self {
+        EnemyType {
+            noun: Noun::common(name),
+            glyph: name.chars().next().unwrap_or('?'),
+            colour: Colour::Green,
+            hp: 10,
+            power: 3,
+            accuracy: 0,
+            evasion: 0,
+            armor: 0,
+            speed: ENERGY_PER_ACTION,
+            flags: flags.to_vec(),
+            min_depth: 1,
+            max_depth: 5,
+            weight: 1,
+            xp: 0,
         }
     }
 }
 
-#[derive(Clone)]
+/// What an enemy is currently up to
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AiState {
+    /// Dozing until it spots the player or hears a noise
+    Asleep,
+    /// Strolling toward a random spot on the level, usually another room
+    Wandering { target: (usize, usize) },
+    /// Chasing the player, or the spot the player was last seen or heard
+    Hunting { last_known: (usize, usize) },
+    /// Running from the player until it has recovered
+    Fleeing,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Enemy {
     pub x: usize,
     pub y: usize,
     pub hp: i32,
     pub max_hp: i32,
     pub power: i32,
+    pub accuracy: i32,
+    pub evasion: i32,
+    pub armor: i32,
     pub enemy_type: EnemyType,
+    /// Builds up by the monster's speed every turn and is spent on actions
+    pub energy: u32,
+    pub state: AiState,
+    /// Where the player last saw this enemy, if they have seen it at all
+    pub last_seen: Option<(usize, usize)>,
 }
 
 impl Enemy {
     pub fn new(x: usize, y: usize, enemy_type: EnemyType) -> Self {
-        let hp = enemy_type.base_hp();
-        let power = enemy_type.base_power();
+        let hp = enemy_type.hp;
         Enemy {
             x,
             y,
             hp,
             max_hp: hp,
-            power,
+            power: enemy_type.power,
+            accuracy: enemy_type.accuracy,
+            evasion: enemy_type.evasion,
+            armor: enemy_type.armor,
             enemy_type,
+            energy: 0,
+            state: AiState::Asleep,
+            last_seen: None,
         }
     }
 
-    pub fn goblin(x: usize, y: usize) -> Self {
-        Enemy::new(x, y, EnemyType::Goblin)
+    pub fn combat_stats(&self) -> CombatStats {
+        CombatStats {
+            power: self.power,
+            accuracy: self.accuracy,
+            evasion: self.evasion,
+            armor: self.armor,
+        }
     }
 
     pub fn take_damage(&mut self, damage: i32) {
@@ -64,39 +162,35 @@ impl Enemy {
         self.hp > 0
     }
 
-    pub fn to_char(&self) -> char {
-        self.enemy_type.to_char()
+    pub fn heal(&mut self, amount: i32) {
+        self.hp = (self.hp + amount).min(self.max_hp);
     }
 
-    pub fn move_toward(&mut self, target_x: usize, target_y: usize, map: &Map, enemies: &[Enemy], self_index: usize, player_x: usize, player_y: usize) {
-        let dx = (target_x as i32 - self.x as i32).signum();
-        let dy = (target_y as i32 - self.y as i32).signum();
-
-        let new_x = (self.x as i32 + dx) as usize;
-        let new_y = (self.y as i32 + dy) as usize;
-
-        // Check if we can move to the new position (not occupied by enemies or player)
-        if map.is_walkable(new_x, new_y) && !Self::position_occupied(new_x, new_y, enemies, self_index, player_x, player_y) {
-            self.x = new_x;
-            self.y = new_y;
-        } else if dx != 0 && map.is_walkable((self.x as i32 + dx) as usize, self.y)
-            && !Self::position_occupied((self.x as i32 + dx) as usize, self.y, enemies, self_index, player_x, player_y) {
-            // Try horizontal only
-            self.x = (self.x as i32 + dx) as usize;
-        } else if dy != 0 && map.is_walkable(self.x, (self.y as i32 + dy) as usize)
-            && !Self::position_occupied(self.x, (self.y as i32 + dy) as usize, enemies, self_index, player_x, player_y) {
-            // Try vertical only
-            self.y = (self.y as i32 + dy) as usize;
-        }
+    pub fn to_glyph(&self) -> Glyph {
+        self.enemy_type.to_glyph()
     }
 
-    fn position_occupied(x: usize, y: usize, enemies: &[Enemy], exclude_index: usize, player_x: usize, player_y: usize) -> bool {
-        if x == player_x && y == player_y {
-            return true;
-        }
-        enemies.iter().enumerate().any(|(i, e)| {
-            i != exclude_index && e.is_alive() && e.x == x && e.y == y
-        })
+    /// Add this turn's energy and return how many actions the enemy gets
+    pub fn gain_energy(&mut self) -> u32 {
+        self.energy += self.enemy_type.speed;
+        let actions = self.energy / ENERGY_PER_ACTION;
+        self.energy %= ENERGY_PER_ACTION;
+        actions
+    }
+
+    /// Low enough on health to run rather than fight, if it's the running kind
+    pub fn is_badly_hurt(&self) -> bool {
+        self.enemy_type.has(Behaviour::Flees) && self.hp * 4 <= self.max_hp
+    }
+
+    /// First step of the A* path to `target`, routing around walls and occupied tiles.
+    /// None when there is no path or the step itself is occupied right now.
+    pub fn path_step<F>(&self, target: (usize, usize), map: &Map, scratch: &mut PathScratch, is_occupied: F) -> Option<(usize, usize)>
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        let path = find_path(map, (self.x, self.y), target, scratch, &is_occupied)?;
+        path.first().copied().filter(|&(x, y)| !is_occupied(x, y))
     }
 
     pub fn distance_to(&self, x: usize, y: usize) -> usize {
@@ -0,0 +1,220 @@
+// Symmetric shadowcasting: a tile is visible from the origin exactly when the
+// origin would be visible from that tile, so monsters and the player always
+// agree on who can see whom.
+
+/// A slope expressed as an exact fraction, `num / den` with `den > 0`
+#[derive(Clone, Copy)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+impl Slope {
+    fn new(num: i32, den: i32) -> Self {
+        Slope { num, den }
+    }
+
+    /// Round `depth * self` to the nearest column, rounding ties up
+    fn round_ties_up(self, depth: i32) -> i32 {
+        (2 * depth * self.num + self.den).div_euclid(2 * self.den)
+    }
+
+    /// Round `depth * self` to the nearest column, rounding ties down
+    fn round_ties_down(self, depth: i32) -> i32 {
+        -(-(2 * depth * self.num - self.den)).div_euclid(2 * self.den)
+    }
+}
+
+/// One row of a quadrant scan, `depth` tiles away from the origin
+#[derive(Clone, Copy)]
+struct Row {
+    depth: i32,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    fn next(self) -> Row {
+        Row { depth: self.depth + 1, ..self }
+    }
+
+    /// Whether the column lies inside this row's sector, so the tile is seen symmetrically
+    fn is_symmetric(self, col: i32) -> bool {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+}
+
+/// Compute every tile visible from `origin` within `radius`.
+/// `is_blocking` is asked about map coordinates (it should return true off the map)
+/// and `mark_visible` is called for every tile that can be seen, which may include
+/// blocking positions just past the map edge.
+pub fn compute_fov<B, M>(origin: (usize, usize), radius: usize, is_blocking: B, mut mark_visible: M)
+where
+    B: Fn(i32, i32) -> bool,
+    M: FnMut(usize, usize),
+{
+    let (ox, oy) = (origin.0 as i32, origin.1 as i32);
+    // Distances are squared in i64 so even a huge radius can't overflow
+    let radius = radius.min(i32::MAX as usize) as i64;
+    mark_visible(origin.0, origin.1);
+
+    // Each quadrant is the direction rows advance in and the direction columns run along
+    let quadrants = [
+        ((0, -1), (1, 0)), // north
+        ((0, 1), (1, 0)),  // south
+        ((1, 0), (0, 1)),  // east
+        ((-1, 0), (0, 1)), // west
+    ];
+
+    for ((depth_dx, depth_dy), (col_dx, col_dy)) in quadrants {
+        let transform = |depth: i32, col: i32| (ox + depth * depth_dx + col * col_dx, oy + depth * depth_dy + col * col_dy);
+
+        let mut rows = vec![Row {
+            depth: 1,
+            start: Slope::new(-1, 1),
+            end: Slope::new(1, 1),
+        }];
+
+        while let Some(mut row) = rows.pop() {
+            if row.depth as i64 > radius {
+                continue;
+            }
+
+            let mut prev_blocking: Option<bool> = None;
+            let min_col = row.start.round_ties_up(row.depth);
+            let max_col = row.end.round_ties_down(row.depth);
+
+            for col in min_col..=max_col {
+                let (x, y) = transform(row.depth, col);
+                let blocking = is_blocking(x, y);
+                let (depth, col_offset) = (row.depth as i64, col as i64);
+                let in_radius = depth * depth + col_offset * col_offset <= radius * radius + radius;
+
+                if in_radius && x >= 0 && y >= 0 && (blocking || row.is_symmetric(col)) {
+                    mark_visible(x as usize, y as usize);
+                }
+
+                // Wall then floor: the visible sector now starts at this tile
+                if prev_blocking == Some(true) && !blocking {
+                    row.start = Slope::new(2 * col - 1, 2 * row.depth);
+                }
+                // Floor to wall transition: scan the visible gap in the next row
+                if prev_blocking == Some(false) && blocking {
+                    let mut next = row.next();
+                    next.end = Slope::new(2 * col - 1, 2 * row.depth);
+                    rows.push(next);
+                }
+
+                prev_blocking = Some(blocking);
+            }
+
+            if prev_blocking == Some(false) {
+                rows.push(row.next());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compute_fov;
+    use crate::map::Tile;
+
+    /// Which tiles of `grid` can be seen from `origin`: `#` is a wall, `+` a closed door,
+    /// `'` an open door and anything else floor
+    fn visible_from(grid: &[&str], origin: (usize, usize), radius: usize) -> Vec<Vec<bool>> {
+        let tiles: Vec<Vec<Tile>> = grid
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .map(|c| match c {
+                        '#' => Tile::Wall,
+                        '+' => Tile::Door,
+                        '\'' => Tile::OpenDoor,
+                        _ => Tile::Floor,
+                    })
+                    .collect()
+            })
+            .collect();
+        let is_blocking = |x: i32, y: i32| {
+            x < 0 || y < 0 || tiles.get(y as usize).and_then(|row| row.get(x as usize)).is_none_or(|t| t.blocks_sight())
+        };
+
+        let mut seen = vec![vec![false; tiles[0].len()]; tiles.len()];
+        compute_fov(origin, radius, is_blocking, |x, y| {
+            if let Some(tile) = seen.get_mut(y).and_then(|row| row.get_mut(x)) {
+                *tile = true;
+            }
+        });
+        seen
+    }
+
+    #[test]
+    fn walls_block_sight() {
+        let seen = visible_from(&["########", "#...#..#", "########"], (1, 1), 10);
+        assert!(seen[1][3]);
+        assert!(seen[1][4], "the wall itself is seen");
+        assert!(!seen[1][5] && !seen[1][6]);
+    }
+
+    #[test]
+    fn closed_doors_block_sight_and_open_ones_do_not() {
+        let closed = visible_from(&["########", "#...+..#", "########"], (1, 1), 10);
+        assert!(closed[1][4]);
+        assert!(!closed[1][5]);
+
+        let open = visible_from(&["########", "#...'..#", "########"], (1, 1), 10);
+        assert!(open[1][5] && open[1][6]);
+    }
+
+    #[test]
+    fn sight_stops_at_the_radius() {
+        let (wall, inside) = ("#".repeat(21), format!("#{}#", ".".repeat(19)));
+        let mut room = vec![wall.as_str()];
+        room.extend([inside.as_str(); 19]);
+        room.push(wall.as_str());
+        let seen = visible_from(&room, (10, 10), 4);
+
+        assert!(seen[10][14] && seen[6][10]);
+        assert!(!seen[10][15] && !seen[5][10]);
+        // Diagonals are measured the same way, 3² + 3² is in and 4² + 3² is out
+        assert!(seen[13][13]);
+        assert!(!seen[13][14]);
+    }
+
+    #[test]
+    fn visibility_is_symmetric() {
+        let grid = [
+            "###########",
+            "#.........#",
+            "#..#...#..#",
+            "#....#....#",
+            "#.#.....#.#",
+            "#...#.#...#",
+            "#.........#",
+            "###########",
+        ];
+        let floor: Vec<(usize, usize)> = grid
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.char_indices().filter(|&(_, c)| c == '.').map(move |(x, _)| (x, y)))
+            .collect();
+
+        for &a in &floor {
+            let from_a = visible_from(&grid, a, 20);
+            for &b in &floor {
+                let from_b = visible_from(&grid, b, 20);
+                assert_eq!(from_a[b.1][b.0], from_b[a.1][a.0], "{:?} and {:?} disagree", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn a_huge_radius_does_not_overflow() {
+        for radius in [100_000, usize::MAX] {
+            let seen = visible_from(&["#####", "#...#", "#####"], (1, 1), radius);
+            assert!(seen[1][3]);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+};
+
+/// A character and the colour it is drawn in; `None` is the terminal's own colour
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Option<Color>,
+}
+
+impl Cell {
+    const BLANK: Cell = Cell { ch: ' ', fg: None };
+}
+
+/// One screenful of characters. The renderer keeps the last frame it drew so
+/// the next one only has to send the cells that changed.
+#[derive(Clone)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<Cell>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Self {
+        Frame {
+            width,
+            height,
+            cells: vec![Cell::BLANK; width * height],
+        }
+    }
+
+    pub fn same_size(&self, other: &Frame) -> bool {
+        self.width == other.width && self.height == other.height
+    }
+
+    /// Set one cell; anything outside the frame is ignored
+    pub fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x] = cell;
+        }
+    }
+
+    /// Write text in the default colour starting at (x, y), cut off at the right edge
+    pub fn print(&mut self, x: usize, y: usize, text: &str) {
+        self.print_coloured(x, y, text, None);
+    }
+
+    pub fn print_coloured(&mut self, x: usize, y: usize, text: &str, fg: Option<Color>) {
+        for (i, ch) in text.chars().enumerate() {
+            self.set(x + i, y, Cell { ch, fg });
+        }
+    }
+
+    pub fn clear_row(&mut self, y: usize) {
+        for x in 0..self.width {
+            self.set(x, y, Cell::BLANK);
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Cell {
+        self.cells[y * self.width + x]
+    }
+
+    /// Queue the commands that turn `previous` into this frame, one `Print` per run
+    /// of changed cells in the same colour. With no previous frame the screen is
+    /// assumed to be blank.
+    pub fn queue_changes(&self, out: &mut impl Write, previous: Option<&Frame>) -> io::Result<()> {
+        let before = |x: usize, y: usize| previous.map_or(Cell::BLANK, |p| p.get(x, y));
+        // Colour the terminal is currently set to, so it is only changed when needed
+        let mut current: Option<Color> = None;
+
+        for y in 0..self.height {
+            let mut x = 0;
+            while x < self.width {
+                let cell = self.get(x, y);
+                if cell == before(x, y) {
+                    x += 1;
+                    continue;
+                }
+
+                let start = x;
+                let mut run = String::new();
+                while x < self.width && self.get(x, y) != before(x, y) && self.get(x, y).fg == cell.fg {
+                    run.push(self.get(x, y).ch);
+                    x += 1;
+                }
+                if cell.fg != current {
+                    match cell.fg {
+                        Some(colour) => queue!(out, SetForegroundColor(colour))?,
+                        None => queue!(out, ResetColor)?,
+                    }
+                    current = cell.fg;
+                }
+                queue!(out, MoveTo(start as u16, y as u16), Print(run))?;
+            }
+        }
+        if current.is_some() {
+            queue!(out, ResetColor)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bytes `queue_changes` sends to turn `previous` into `frame`
+    fn changes(frame: &Frame, previous: Option<&Frame>) -> Vec<u8> {
+        let mut out = Vec::new();
+        frame.queue_changes(&mut out, previous).unwrap();
+        out
+    }
+
+    #[test]
+    fn identical_frames_send_nothing() {
+        let mut frame = Frame::new(10, 3);
+        frame.print_coloured(1, 1, "hello", Some(Color::Red));
+        assert!(changes(&frame, Some(&frame.clone())).is_empty());
+        assert!(changes(&Frame::new(10, 3), None).is_empty(), "a blank frame matches a blank screen");
+    }
+
+    #[test]
+    fn a_single_changed_cell_is_one_move_and_print() {
+        let previous = Frame::new(10, 3);
+        let mut frame = previous.clone();
+        frame.print(3, 1, "x");
+
+        let mut expected = Vec::new();
+        queue!(expected, MoveTo(3, 1), Print("x".to_string())).unwrap();
+        assert_eq!(changes(&frame, Some(&previous)), expected);
+    }
+
+    #[test]
+    fn a_colour_change_splits_the_run_and_the_colour_is_reset_last() {
+        let previous = Frame::new(10, 3);
+        let mut frame = previous.clone();
+        frame.print(2, 0, "ab");
+        frame.print_coloured(4, 0, "cd", Some(Color::Red));
+
+        let mut expected = Vec::new();
+        queue!(
+            expected,
+            MoveTo(2, 0),
+            Print("ab".to_string()),
+            SetForegroundColor(Color::Red),
+            MoveTo(4, 0),
+            Print("cd".to_string()),
+            ResetColor
+        )
+        .unwrap();
+        assert_eq!(changes(&frame, Some(&previous)), expected);
+    }
+}
@@ -0,0 +1,438 @@
+use std::path::PathBuf;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::action::Action;
+use crate::ai::{self, Intent, Surroundings};
+use crate::combat::{enemy_attack, player_attack, CombatResult};
+use crate::config::Config;
+use crate::dijkstra::DijkstraMap;
+use crate::dungeon::{Dungeon, Level};
+use crate::item::FloorItem;
+use crate::map::{Map, Tile};
+use crate::messages::MessageLog;
+use crate::player::Player;
+use crate::replay::Replay;
+use crate::roster::Roster;
+use crate::save;
+
+/// Map area for each room tried, giving 12 rooms on the default 100x35 map
+const AREA_PER_ROOM: usize = 290;
+const MIN_ROOMS: usize = 2;
+const MIN_ROOM_SIZE: usize = 4;
+const MAX_ROOM_SIZE: usize = 8;
+/// How many steps away enemies can hear the player walking
+pub(crate) const STEP_NOISE: usize = 2;
+/// How many steps away enemies can hear a fight
+pub(crate) const FIGHT_NOISE: usize = 10;
+const DUNGEON_DEPTH: usize = 5;
+
+/// Something that happened while applying an action, for frontends to react to
+pub enum GameEvent {
+    /// A line for the message log
+    Message(String),
+    /// A blow struck by or at the player
+    Combat(CombatResult),
+    LevelUp { level: u32 },
+    DepthChanged { depth: usize },
+    /// The player died and the run is over
+    PlayerDied,
+    /// The deepest level is cleared and the run is over
+    Victory,
+}
+
+/// Everything about a run in progress, all of which goes into a save
+#[derive(Serialize, Deserialize)]
+pub struct GameState {
+    dungeon: Dungeon,
+    player: Player,
+    messages: MessageLog,
+    rng: ChaCha8Rng,
+    config: Config,
+    roster: Roster,
+    /// How far the player's action this turn could be heard
+    noise: usize,
+    /// Turns the player has spent so far
+    turns: u32,
+    /// Every action taken so far, from the start of the run
+    replay: Replay,
+    /// Raised by the action being applied, handed back once it is done
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+}
+
+impl GameState {
+    /// Start a new run, loading the monster roster named in the config
+    pub fn new(config: Config) -> Result<Self, String> {
+        let roster = Roster::load(config.monsters.as_deref())?;
+        Ok(GameState::with_roster(config, roster))
+    }
+
+    /// Start a new run with an already loaded monster roster
+    pub fn with_roster(config: Config, roster: Roster) -> Self {
+        // Every random roll in the game comes from this one seeded generator
+        let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+
+        // Generate the first level; deeper ones are generated on the way down
+        let level = generate_level(&mut rng, &config, &roster, 1, &mut Vec::new());
+
+        // Spawn player at the level's start
+        let (px, py) = level.map.player_spawn();
+        let player = Player::new(px, py);
+
+        let replay = Replay::new(&config, &roster);
+        let mut game = GameState {
+            dungeon: Dungeon::new(level),
+            player,
+            messages: MessageLog::default(),
+            rng,
+            config,
+            roster,
+            noise: 0,
+            turns: 0,
+            replay,
+            events: Vec::new(),
+        };
+
+        // Look around the starting area
+        game.update_fov();
+        game
+    }
+
+    /// Pick up the saved game where it was left off
+    pub fn load() -> Result<Self, String> {
+        let mut game: GameState = save::read()?;
+        game.messages.add("Welcome back.".to_string());
+        Ok(game)
+    }
+
+    /// Write the whole run to the save file, returning where it went
+    pub fn save(&self) -> Result<PathBuf, String> {
+        save::write(self)
+    }
+
+    /// Carry out one player action, letting the enemies act if it took a turn,
+    /// and return everything that happened. Every action is recorded in the replay.
+    pub fn apply(&mut self, action: Action) -> Vec<GameEvent> {
+        if self.is_over() {
+            return Vec::new();
+        }
+        if action != Action::None {
+            self.replay.actions.push(action);
+        }
+
+        let turn_spent = match action {
+            Action::Move(dx, dy) => {
+                self.handle_player_move(dx, dy);
+                true
+            }
+            Action::Descend | Action::Ascend => self.take_stairs(action == Action::Descend),
+            Action::PickUp => self.pick_up(),
+            Action::UseItem(slot) => self.use_item(slot),
+            Action::DropItem(slot) => self.drop_item(slot),
+            // Opening screens, saving and quitting are up to the frontend
+            Action::Inventory | Action::Save | Action::Quit | Action::None => false,
+        };
+
+        if turn_spent {
+            self.turns += 1;
+            if self.player.is_alive() {
+                self.enemy_turns();
+            }
+            if !self.player.is_alive() {
+                self.events.push(GameEvent::PlayerDied);
+            } else if self.is_won() {
+                self.events.push(GameEvent::Victory);
+            }
+        }
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
+    /// The level the player is on
+    pub fn level(&self) -> &Level {
+        self.dungeon.current()
+    }
+
+    pub fn dungeon(&self) -> &Dungeon {
+        &self.dungeon
+    }
+
+    /// Current depth, starting at 1 for the top level
+    pub fn depth(&self) -> usize {
+        self.dungeon.depth()
+    }
+
+    pub fn messages(&self) -> &MessageLog {
+        &self.messages
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn turns(&self) -> u32 {
+        self.turns
+    }
+
+    /// Every action taken so far, ready to be written out as a replay
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Whether the player has died or won; no further actions are taken once it is
+    pub fn is_over(&self) -> bool {
+        !self.player.is_alive() || self.is_won()
+    }
+
+    fn is_won(&self) -> bool {
+        self.dungeon.depth() == DUNGEON_DEPTH && self.all_enemies_dead()
+    }
+
+    /// Add a line to the message log and pass it on to the frontend
+    fn say(&mut self, message: String) {
+        self.messages.add(message.clone());
+        self.events.push(GameEvent::Message(message));
+    }
+
+    fn handle_player_move(&mut self, dx: i32, dy: i32) {
+        let new_x = (self.player.x as i32 + dx) as usize;
+        let new_y = (self.player.y as i32 + dy) as usize;
+
+        let enemy_idx = self.enemy_at(new_x, new_y);
+        let level = self.dungeon.current_mut();
+
+        // Check for enemy at target position
+        if let Some(enemy_idx) = enemy_idx {
+            self.noise = FIGHT_NOISE;
+            let result = player_attack(&mut self.rng, &self.player, &mut level.enemies[enemy_idx]);
+            let xp = if result.killed { level.enemies[enemy_idx].enemy_type.xp } else { 0 };
+            self.report_combat(result);
+
+            if self.player.gain_xp(xp) > 0 {
+                self.say("You feel stronger!".to_string());
+                self.events.push(GameEvent::LevelUp { level: self.player.level });
+            }
+        } else if level.map.is_walkable(new_x, new_y) {
+            self.player.move_by(dx, dy);
+            self.noise = STEP_NOISE;
+
+            level.map.open_door(new_x, new_y);
+            level.map.update_fov(new_x, new_y, self.config.fov_radius);
+            level.remember_enemies();
+
+            if let Some(i) = level.item_at(new_x, new_y) {
+                let noun = level.items[i].item.noun();
+                self.say(format!("You see {} here.", noun.indefinite()));
+            }
+        }
+    }
+
+    /// Report a blow both as a message and as a combat event
+    fn report_combat(&mut self, result: CombatResult) {
+        self.say(result.message.clone());
+        self.events.push(GameEvent::Combat(result));
+    }
+
+    /// Pick up the item under the player, returning true if a turn was spent
+    fn pick_up(&mut self) -> bool {
+        let level = self.dungeon.current_mut();
+        let Some(i) = level.item_at(self.player.x, self.player.y) else {
+            self.say("There is nothing here to pick up.".to_string());
+            return false;
+        };
+
+        let item = level.items[i].item;
+        if self.player.add_item(item).is_err() {
+            self.say("Your pack is full.".to_string());
+            return false;
+        }
+        level.items.remove(i);
+        self.say(format!("You pick up {}.", item.noun().indefinite()));
+        true
+    }
+
+    /// Use or equip the item in an inventory slot, returning true if a turn was spent
+    fn use_item(&mut self, slot: usize) -> bool {
+        let Some(&item) = self.player.inventory.get(slot) else {
+            return false;
+        };
+        let noun = item.noun();
+
+        if let Some(equip_slot) = item.slot() {
+            self.player.inventory.remove(slot);
+            let message = match self.player.equip(item, equip_slot) {
+                Some(old) => {
+                    self.player.inventory.push(old);
+                    format!("You swap {} for {}.", old.noun().definite(), noun.definite())
+                }
+                None => format!("You equip {}.", noun.definite()),
+            };
+            self.say(message);
+            return true;
+        }
+
+        let healing = item.healing();
+        if self.player.hp >= self.player.max_hp() {
+            self.say("You are already at full health.".to_string());
+            return false;
+        }
+
+        self.player.inventory.remove(slot);
+        self.player.heal(healing);
+        self.say(format!("You drink {} and restore {} HP!", noun.definite(), healing));
+        true
+    }
+
+    /// Drop the item in an inventory slot, returning true if a turn was spent
+    fn drop_item(&mut self, slot: usize) -> bool {
+        if slot >= self.player.inventory.len() {
+            return false;
+        }
+        let item = self.player.inventory.remove(slot);
+        let (x, y) = (self.player.x, self.player.y);
+        self.dungeon.current_mut().items.push(FloorItem { x, y, item });
+        self.say(format!("You drop {}.", item.noun().definite()));
+        true
+    }
+
+    /// Take the stairs under the player, returning true if a turn was spent
+    fn take_stairs(&mut self, down: bool) -> bool {
+        let (x, y) = (self.player.x, self.player.y);
+        let map = &self.dungeon.current().map;
+        let depth = self.dungeon.depth();
+
+        if down && !map.is_stairs_down(x, y) {
+            self.say("There are no stairs down here.".to_string());
+            return false;
+        }
+        if !down && !map.is_stairs_up(x, y) {
+            self.say("There are no stairs up here.".to_string());
+            return false;
+        }
+
+        let new_depth = if down { depth + 1 } else { depth - 1 };
+        // Nothing moves on a level the player isn't on, so its wander maps aren't needed
+        self.dungeon.current_mut().wander_maps.clear();
+        if !self.dungeon.has_level(new_depth) {
+            let mut uniques = self.dungeon.unique_names();
+            let level = generate_level(&mut self.rng, &self.config, &self.roster, new_depth, &mut uniques);
+            self.dungeon.add_level(level);
+        }
+        self.dungeon.set_depth(new_depth);
+
+        // Arrive on the matching staircase of the new level
+        let arrival = if down { Tile::StairsUp } else { Tile::StairsDown };
+        let map = &self.dungeon.current().map;
+        let (px, py) = map.find_tile(arrival).unwrap_or_else(|| map.player_spawn());
+        let (px, py) = self.free_tile_near(px, py);
+        self.player.x = px;
+        self.player.y = py;
+        self.update_fov();
+
+        let verb = if down { "descend" } else { "climb" };
+        self.say(format!("You {} to depth {}.", verb, new_depth));
+        self.events.push(GameEvent::DepthChanged { depth: new_depth });
+        true
+    }
+
+    /// The given tile if no enemy stands on it, otherwise the first free walkable neighbour
+    fn free_tile_near(&self, x: usize, y: usize) -> (usize, usize) {
+        let map = &self.dungeon.current().map;
+        for (dx, dy) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let nx = (x as i32 + dx) as usize;
+            let ny = (y as i32 + dy) as usize;
+            if map.is_walkable(nx, ny) && self.enemy_at(nx, ny).is_none() {
+                return (nx, ny);
+            }
+        }
+        (x, y)
+    }
+
+    fn update_fov(&mut self) {
+        let (x, y) = (self.player.x, self.player.y);
+        let level = self.dungeon.current_mut();
+        level.map.update_fov(x, y, self.config.fov_radius);
+        level.remember_enemies();
+    }
+
+    fn enemy_turns(&mut self) {
+        let player = (self.player.x, self.player.y);
+        let level = self.dungeon.current_mut();
+
+        // Shared map for this turn; every enemy reads it instead of routing on its own
+        let chase_map = DijkstraMap::new(&level.map, &[player]);
+        level.forget_unused_wander_maps();
+        let world = Surroundings::new(&level.map, player, &chase_map, &level.wander_maps, self.noise);
+
+        // Who stands where, kept up to date as enemies move
+        let mut occupied = level.occupancy();
+        occupied[player.1][player.0] = true;
+        let mut opened_doors = Vec::new();
+
+        for i in 0..level.enemies.len() {
+            if !level.enemies[i].is_alive() {
+                continue;
+            }
+
+            // Fast monsters may act more than once a turn, slow ones skip turns
+            for _ in 0..level.enemies[i].gain_energy() {
+                let (x, y) = (level.enemies[i].x, level.enemies[i].y);
+                let intent = ai::take_turn(&mut level.enemies[i], &world, &mut self.rng, |x, y| occupied[y][x]);
+
+                match intent {
+                    Intent::Move(new_x, new_y) => {
+                        occupied[y][x] = false;
+                        occupied[new_y][new_x] = true;
+                        level.enemies[i].x = new_x;
+                        level.enemies[i].y = new_y;
+                        opened_doors.push((new_x, new_y));
+                    }
+                    Intent::Attack => {
+                        let result = enemy_attack(&mut self.rng, &level.enemies[i], &mut self.player);
+                        self.messages.add(result.message.clone());
+                        self.events.push(GameEvent::Message(result.message.clone()));
+                        self.events.push(GameEvent::Combat(result));
+                    }
+                    Intent::Wait => {}
+                }
+            }
+        }
+
+        for (x, y) in opened_doors {
+            level.map.open_door(x, y);
+        }
+        self.noise = 0;
+
+        // Doors opened by enemies may have changed what the player can see
+        self.update_fov();
+    }
+
+    fn enemy_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.dungeon.current().enemies.iter().position(|e| e.is_alive() && e.x == x && e.y == y)
+    }
+
+    fn all_enemies_dead(&self) -> bool {
+        self.dungeon.current().enemies.iter().all(|e| !e.is_alive())
+    }
+}
+
+/// Generate the level at `depth`, with stairs up to the level above and down to the one below,
+/// with any unique monsters it spawns added to `uniques`
+fn generate_level(rng: &mut ChaCha8Rng, config: &Config, roster: &Roster, depth: usize, uniques: &mut Vec<String>) -> Level {
+    let mut map = Map::new(config.map_width, config.map_height);
+    let num_rooms = (config.map_width * config.map_height / AREA_PER_ROOM).max(MIN_ROOMS);
+    map.generate(rng, config.generator, num_rooms, MIN_ROOM_SIZE, MAX_ROOM_SIZE, config.loop_ratio);
+    map.place_stairs(depth > 1, depth < DUNGEON_DEPTH);
+    let mut level = Level::new(map, rng, roster, depth, uniques);
+    if depth == DUNGEON_DEPTH {
+        // Clearing this level wins the game, so an empty one would be won on arrival
+        level.add_guardian(rng, roster, depth, uniques);
+    }
+    level
+}
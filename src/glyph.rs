@@ -0,0 +1,170 @@
+use std::env;
+
+use serde::{Deserialize, Serialize};
+
+/// A palette entry; each theme decides which terminal colour it is drawn in
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Colour {
+    Wall,
+    Floor,
+    Corridor,
+    Door,
+    Stairs,
+    Player,
+    Potion,
+    Gear,
+    /// Where an enemy was last seen
+    Marker,
+    // Plain colours monsters can pick in the roster
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Brown,
+}
+
+impl Colour {
+    pub const ALL: [Colour; 17] = [
+        Colour::Wall,
+        Colour::Floor,
+        Colour::Corridor,
+        Colour::Door,
+        Colour::Stairs,
+        Colour::Player,
+        Colour::Potion,
+        Colour::Gear,
+        Colour::Marker,
+        Colour::Red,
+        Colour::Green,
+        Colour::Yellow,
+        Colour::Blue,
+        Colour::Magenta,
+        Colour::Cyan,
+        Colour::White,
+        Colour::Brown,
+    ];
+}
+
+/// A character to draw and the palette colour to draw it in
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Glyph {
+    pub ch: char,
+    pub colour: Colour,
+}
+
+impl Glyph {
+    /// Nothing at all, for tiles that haven't been seen
+    pub const BLANK: Glyph = Glyph::new(' ', Colour::Floor);
+
+    pub const fn new(ch: char, colour: Colour) -> Self {
+        Glyph { ch, colour }
+    }
+}
+
+/// Every character the map can be drawn with
+pub struct GlyphSet {
+    /// Walls by which neighbours are walls too: up 1, down 2, left 4, right 8
+    pub walls: [char; 16],
+    pub floor: char,
+    pub corridor: char,
+    pub door: char,
+    pub open_door: char,
+    pub stairs_down: char,
+    pub stairs_up: char,
+    pub potion: char,
+    pub weapon: char,
+    pub armor: char,
+    pub shield: char,
+    pub ring: char,
+    /// The filled and empty parts of a health bar
+    pub bar_full: char,
+    pub bar_empty: char,
+}
+
+/// Box-drawing walls and a few symbols outside ASCII
+pub const UNICODE: GlyphSet = GlyphSet {
+    walls: [
+        '#', '│', '│', '│', '─', '┘', '┐', '┤',
+        '─', '└', '┌', '├', '─', '┴', '┬', '┼',
+    ],
+    floor: '·',
+    corridor: ':',
+    door: '╬',
+    open_door: '\'',
+    stairs_down: '>',
+    stairs_up: '<',
+    potion: '♥',
+    weapon: '/',
+    armor: '[',
+    shield: ')',
+    ring: '=',
+    bar_full: '█',
+    bar_empty: '░',
+};
+
+/// Nothing but 7-bit ASCII, for terminals and logs that mangle anything else
+pub const ASCII: GlyphSet = GlyphSet {
+    walls: ['#'; 16],
+    floor: '.',
+    corridor: ':',
+    door: '+',
+    open_door: '\'',
+    stairs_down: '>',
+    stairs_up: '<',
+    potion: '!',
+    weapon: '/',
+    armor: '[',
+    shield: ')',
+    ring: '=',
+    bar_full: '#',
+    bar_empty: '-',
+};
+
+/// Which glyph set to draw with
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Charset {
+    #[default]
+    Unicode,
+    Ascii,
+}
+
+impl Charset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unicode" => Some(Charset::Unicode),
+            "ascii" => Some(Charset::Ascii),
+            "auto" => Some(Charset::detect()),
+            _ => None,
+        }
+    }
+
+    /// Unicode if the locale says the terminal speaks UTF-8, otherwise ASCII
+    pub fn detect() -> Self {
+        if cfg!(windows) {
+            return Charset::Unicode;
+        }
+        // The first of these that is set wins, as with setlocale
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        if locale.contains("utf-8") || locale.contains("utf8") {
+            Charset::Unicode
+        } else {
+            Charset::Ascii
+        }
+    }
+
+    pub fn glyphs(self) -> &'static GlyphSet {
+        match self {
+            Charset::Unicode => &UNICODE,
+            Charset::Ascii => &ASCII,
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+/// How to refer to a kind of creature in messages
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Noun {
+    pub name: String,
+    /// Indefinite article, when "a"/"an" from the first letter gets it wrong
+    #[serde(default)]
+    pub article: Option<String>,
+    /// A uniquely named individual such as "Grishnak", used without any article
+    #[serde(default)]
+    pub unique: bool,
+    /// Plural form, when adding "s" or "es" to the name gets it wrong
+    #[serde(default)]
+    pub plural: Option<String>,
+}
+
+impl Noun {
+    /// An ordinary noun whose article follows the usual rules
+    pub fn common(name: &str) -> Self {
+        Noun {
+            name: name.to_string(),
+            article: None,
+            unique: false,
+            plural: None,
+        }
+    }
+
+    /// "the goblin", or just the name for unique monsters
+    pub fn definite(&self) -> String {
+        if self.unique {
+            self.name.clone()
+        } else {
+            format!("the {}", self.name)
+        }
+    }
+
+    /// "a goblin", "an imp", or just the name for unique monsters
+    pub fn indefinite(&self) -> String {
+        if self.unique {
+            return self.name.clone();
+        }
+        let article = self.article.clone().unwrap_or_else(|| indefinite_article(&self.name).to_string());
+        format!("{} {}", article, self.name)
+    }
+
+    /// "goblins", "wolves", or just the name for unique monsters
+    pub fn plural(&self) -> String {
+        if self.unique {
+            return self.name.clone();
+        }
+        self.plural.clone().unwrap_or_else(|| regular_plural(&self.name))
+    }
+}
+
+fn indefinite_article(word: &str) -> &'static str {
+    match word.chars().next().map(|c| c.to_ascii_lowercase()) {
+        Some('a' | 'e' | 'i' | 'o' | 'u') => "an",
+        _ => "a",
+    }
+}
+
+/// Add "es" after hissing sounds, turn a consonant-y ending into "ies", otherwise add "s"
+fn regular_plural(word: &str) -> String {
+    let lower = word.to_ascii_lowercase();
+    if ["s", "x", "z", "ch", "sh"].iter().any(|end| lower.ends_with(end)) {
+        return format!("{}es", word);
+    }
+    if let Some(stem) = word.strip_suffix(['y', 'Y']) {
+        if !stem.ends_with(['a', 'e', 'i', 'o', 'u', 'A', 'E', 'I', 'O', 'U']) {
+            return format!("{}ies", stem);
+        }
+    }
+    format!("{}s", word)
+}
+
+/// Upper-case the first letter, for nouns that start a sentence
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique(name: &str) -> Noun {
+        Noun { unique: true, ..Noun::common(name) }
+    }
+
+    #[test]
+    fn picks_the_article_from_the_first_letter() {
+        assert_eq!(indefinite_article("goblin"), "a");
+        assert_eq!(indefinite_article("imp"), "an");
+        assert_eq!(indefinite_article("Orc"), "an");
+        assert_eq!(indefinite_article(""), "a");
+    }
+
+    #[test]
+    fn an_explicit_article_wins_over_the_guess() {
+        let noun = Noun { article: Some("a".to_string()), ..Noun::common("unicorn") };
+        assert_eq!(noun.indefinite(), "a unicorn");
+        assert_eq!(noun.definite(), "the unicorn");
+    }
+
+    #[test]
+    fn unique_monsters_go_without_articles() {
+        let noun = unique("Grishnak");
+        assert_eq!(noun.definite(), "Grishnak");
+        assert_eq!(noun.indefinite(), "Grishnak");
+        assert_eq!(noun.plural(), "Grishnak");
+    }
+
+    #[test]
+    fn regular_plurals_follow_the_spelling_rules() {
+        assert_eq!(Noun::common("goblin").plural(), "goblins");
+        assert_eq!(Noun::common("lynx").plural(), "lynxes");
+        assert_eq!(Noun::common("witch").plural(), "witches");
+        assert_eq!(Noun::common("harpy").plural(), "harpies");
+        assert_eq!(Noun::common("monkey").plural(), "monkeys");
+    }
+
+    #[test]
+    fn an_explicit_plural_wins_over_the_rule() {
+        let noun = Noun { plural: Some("wolves".to_string()), ..Noun::common("wolf") };
+        assert_eq!(noun.plural(), "wolves");
+    }
+}
@@ -1,17 +1,36 @@
+use std::time::Duration;
+
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use worldfall::Action;
+
+/// Keys understood while the inventory screen is open
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InventoryAction {
+    Up,
+    Down,
+    Use,
+    Drop,
+    Close,
+    None,
+}
 
+/// Keys understood while a replay is playing
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Action {
-    Move(i32, i32),
-    Quit,
+pub enum ReplayControl {
+    Faster,
+    Slower,
+    Stop,
+    /// The terminal changed size and the screen needs laying out again
+    Resize,
     None,
 }
 
 pub fn get_input() -> Action {
-    if let Ok(Event::Key(key_event)) = event::read() {
-        return handle_key_event(key_event);
+    match event::read() {
+        Ok(Event::Key(key_event)) => handle_key_event(key_event),
+        // Anything else, resizes included, just redraws the screen for its current size
+        _ => Action::None,
     }
-    Action::None
 }
 
 fn handle_key_event(event: KeyEvent) -> Action {
@@ -22,12 +41,23 @@ fn handle_key_event(event: KeyEvent) -> Action {
         KeyCode::Left => Action::Move(-1, 0),
         KeyCode::Right => Action::Move(1, 0),
 
+        // Save and quit; checked before WASD so Ctrl+S isn't a step down
+        KeyCode::Char('s') if event.modifiers.contains(KeyModifiers::CONTROL) => Action::Save,
+
         // WASD keys
         KeyCode::Char('w') | KeyCode::Char('W') => Action::Move(0, -1),
         KeyCode::Char('s') | KeyCode::Char('S') => Action::Move(0, 1),
         KeyCode::Char('a') | KeyCode::Char('A') => Action::Move(-1, 0),
         KeyCode::Char('d') | KeyCode::Char('D') => Action::Move(1, 0),
 
+        // Stairs
+        KeyCode::Char('>') => Action::Descend,
+        KeyCode::Char('<') => Action::Ascend,
+
+        // Items
+        KeyCode::Char('g') | KeyCode::Char('G') => Action::PickUp,
+        KeyCode::Char('i') | KeyCode::Char('I') => Action::Inventory,
+
         // Quit
         KeyCode::Char('q') | KeyCode::Char('Q') => Action::Quit,
         KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => Action::Quit,
@@ -37,6 +67,42 @@ fn handle_key_event(event: KeyEvent) -> Action {
     }
 }
 
+pub fn get_inventory_input() -> InventoryAction {
+    if let Ok(Event::Key(key_event)) = event::read() {
+        return match key_event.code {
+            KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('W') => InventoryAction::Up,
+            KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('S') => InventoryAction::Down,
+            KeyCode::Enter | KeyCode::Char('u') | KeyCode::Char('U') => InventoryAction::Use,
+            KeyCode::Char('d') | KeyCode::Char('D') => InventoryAction::Drop,
+            KeyCode::Esc | KeyCode::Char('i') | KeyCode::Char('I') => InventoryAction::Close,
+            _ => InventoryAction::None,
+        };
+    }
+    InventoryAction::None
+}
+
+/// Wait up to `timeout` for a replay control key
+pub fn poll_replay_control(timeout: Duration) -> ReplayControl {
+    if !event::poll(timeout).unwrap_or(false) {
+        return ReplayControl::None;
+    }
+    match event::read() {
+        Ok(Event::Key(key_event)) => match key_event.code {
+            KeyCode::Char('+') | KeyCode::Char('=') => ReplayControl::Faster,
+            KeyCode::Char('-') => ReplayControl::Slower,
+            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => ReplayControl::Stop,
+            _ => ReplayControl::None,
+        },
+        Ok(Event::Resize(..)) => ReplayControl::Resize,
+        _ => ReplayControl::None,
+    }
+}
+
+/// Block until a key is pressed, ignoring resizes and other events
 pub fn wait_for_key() {
-    let _ = event::read();
+    while let Ok(event) = event::read() {
+        if matches!(event, Event::Key(_)) {
+            break;
+        }
+    }
 }
@@ -0,0 +1,173 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::glyph::{Colour, Glyph, GlyphSet};
+use crate::grammar::Noun;
+
+/// Hit points restored by a health potion
+const POTION_HEAL: i32 = 5;
+
+/// Every item that can turn up on the floor: the item, the shallowest depth
+/// it appears at and its relative chance of being picked
+const ITEM_TABLE: &[(Item, usize, u32)] = &[
+    (Item::HealthPotion, 1, 12),
+    (Item::Dagger, 1, 3),
+    (Item::LeatherJerkin, 1, 3),
+    (Item::Buckler, 1, 3),
+    (Item::Sword, 2, 2),
+    (Item::RingOfVitality, 2, 1),
+    (Item::ChainMailShirt, 3, 2),
+    (Item::KiteShield, 3, 2),
+    (Item::RingOfProtection, 3, 1),
+    (Item::BattleAxe, 4, 1),
+];
+
+/// Something the player can pick up and carry
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    HealthPotion,
+    Dagger,
+    Sword,
+    BattleAxe,
+    LeatherJerkin,
+    ChainMailShirt,
+    Buckler,
+    KiteShield,
+    RingOfVitality,
+    RingOfProtection,
+}
+
+/// Where a piece of equipment is worn
+#[derive(Clone, Copy, PartialEq)]
+pub enum Slot {
+    Weapon,
+    Armor,
+    Shield,
+    Ring,
+}
+
+/// What a piece of equipment adds to the wearer's stats
+#[derive(Clone, Copy, Default)]
+pub struct Bonuses {
+    pub power: i32,
+    pub accuracy: i32,
+    pub evasion: i32,
+    pub armor: i32,
+    pub max_hp: i32,
+}
+
+impl Item {
+    /// Pick an item allowed at `depth`, weighted by how common each item is
+    pub fn random<R: Rng>(rng: &mut R, depth: usize) -> Item {
+        let allowed: Vec<_> = ITEM_TABLE.iter().filter(|(_, min_depth, _)| *min_depth <= depth).collect();
+        allowed
+            .choose_weighted(rng, |(_, _, weight)| *weight)
+            .map(|(item, _, _)| *item)
+            .unwrap_or(Item::HealthPotion)
+    }
+
+    pub fn noun(self) -> Noun {
+        let name = match self {
+            Item::HealthPotion => "health potion",
+            Item::Dagger => "dagger",
+            Item::Sword => "sword",
+            Item::BattleAxe => "battle axe",
+            Item::LeatherJerkin => "leather jerkin",
+            Item::ChainMailShirt => "chain mail shirt",
+            Item::Buckler => "buckler",
+            Item::KiteShield => "kite shield",
+            Item::RingOfVitality => "ring of vitality",
+            Item::RingOfProtection => "ring of protection",
+        };
+        Noun::common(name)
+    }
+
+    pub fn to_glyph(self, glyphs: &GlyphSet) -> Glyph {
+        match self.slot() {
+            None => Glyph::new(glyphs.potion, Colour::Potion),
+            Some(Slot::Weapon) => Glyph::new(glyphs.weapon, Colour::Gear),
+            Some(Slot::Armor) => Glyph::new(glyphs.armor, Colour::Gear),
+            Some(Slot::Shield) => Glyph::new(glyphs.shield, Colour::Gear),
+            Some(Slot::Ring) => Glyph::new(glyphs.ring, Colour::Gear),
+        }
+    }
+
+    /// Hit points restored by using this item
+    pub fn healing(self) -> i32 {
+        match self {
+            Item::HealthPotion => POTION_HEAL,
+            _ => 0,
+        }
+    }
+
+    /// The slot this item is worn in, or None if it isn't equipment
+    pub fn slot(self) -> Option<Slot> {
+        match self {
+            Item::HealthPotion => None,
+            Item::Dagger | Item::Sword | Item::BattleAxe => Some(Slot::Weapon),
+            Item::LeatherJerkin | Item::ChainMailShirt => Some(Slot::Armor),
+            Item::Buckler | Item::KiteShield => Some(Slot::Shield),
+            Item::RingOfVitality | Item::RingOfProtection => Some(Slot::Ring),
+        }
+    }
+
+    pub fn bonuses(self) -> Bonuses {
+        let none = Bonuses::default();
+        match self {
+            Item::HealthPotion => none,
+            Item::Dagger => Bonuses { power: 1, accuracy: 10, ..none },
+            Item::Sword => Bonuses { power: 3, ..none },
+            Item::BattleAxe => Bonuses { power: 5, accuracy: -10, ..none },
+            Item::LeatherJerkin => Bonuses { armor: 1, ..none },
+            Item::ChainMailShirt => Bonuses { armor: 2, evasion: -5, ..none },
+            Item::Buckler => Bonuses { evasion: 5, ..none },
+            Item::KiteShield => Bonuses { armor: 1, evasion: 5, accuracy: -5, ..none },
+            Item::RingOfVitality => Bonuses { max_hp: 5, ..none },
+            Item::RingOfProtection => Bonuses { armor: 1, evasion: 5, ..none },
+        }
+    }
+}
+
+/// What the player is wearing in each slot
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Equipment {
+    pub weapon: Option<Item>,
+    pub armor: Option<Item>,
+    pub shield: Option<Item>,
+    pub ring: Option<Item>,
+}
+
+impl Equipment {
+    pub fn slot_mut(&mut self, slot: Slot) -> &mut Option<Item> {
+        match slot {
+            Slot::Weapon => &mut self.weapon,
+            Slot::Armor => &mut self.armor,
+            Slot::Shield => &mut self.shield,
+            Slot::Ring => &mut self.ring,
+        }
+    }
+
+    /// Every slot's bonuses added together
+    pub fn bonuses(&self) -> Bonuses {
+        [self.weapon, self.armor, self.shield, self.ring]
+            .into_iter()
+            .flatten()
+            .map(Item::bonuses)
+            .fold(Bonuses::default(), |total, b| Bonuses {
+                power: total.power + b.power,
+                accuracy: total.accuracy + b.accuracy,
+                evasion: total.evasion + b.evasion,
+                armor: total.armor + b.armor,
+                max_hp: total.max_hp + b.max_hp,
+            })
+    }
+}
+
+/// An item lying on the floor of a level
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct FloorItem {
+    pub x: usize,
+    pub y: usize,
+    pub item: Item,
+}
@@ -0,0 +1,28 @@
+pub mod action;
+pub mod combat;
+pub mod config;
+pub mod dungeon;
+pub mod enemy;
+pub mod frame;
+pub mod game;
+pub mod glyph;
+pub mod grammar;
+pub mod item;
+pub mod map;
+pub mod messages;
+pub mod player;
+pub mod render;
+pub mod replay;
+pub mod roster;
+pub mod save;
+pub mod target;
+pub mod theme;
+mod ai;
+mod camera;
+mod dijkstra;
+mod fov;
+mod pathfinding;
+
+pub use action::Action;
+pub use config::Config;
+pub use game::{GameEvent, GameState};
@@ -1,190 +1,170 @@
-mod map;
-mod player;
-mod enemy;
-mod combat;
-mod render;
 mod input;
-
-use map::Map;
-use player::Player;
-use enemy::Enemy;
-use render::Renderer;
-use input::{get_input, wait_for_key, Action};
-use combat::{player_attack, enemy_attack};
-
-const MAP_WIDTH: usize = 100;
-const MAP_HEIGHT: usize = 35;
-const NUM_ROOMS: usize = 12;
-const MIN_ROOM_SIZE: usize = 4;
-const MAX_ROOM_SIZE: usize = 8;
-const ENEMY_CHASE_RANGE: usize = 8;
-
-struct Game {
-    map: Map,
-    player: Player,
-    enemies: Vec<Enemy>,
-    renderer: Renderer,
+mod playback;
+
+use std::io;
+
+use worldfall::render::Renderer;
+use worldfall::replay::Replay;
+use worldfall::target::TerminalTarget;
+use worldfall::theme::Theme;
+use worldfall::{Action, Config, GameEvent, GameState};
+
+use input::{get_input, get_inventory_input, wait_for_key, InventoryAction};
+use playback::Playback;
+
+/// The terminal frontend: draws the game, reads the keyboard or a replay,
+/// and hands the resulting actions to the game
+struct Terminal {
+    game: GameState,
+    renderer: Renderer<TerminalTarget>,
+    /// The recorded run being played back, if this is a replay
+    playback: Option<Playback>,
     running: bool,
 }
 
-impl Game {
-    fn new() -> Self {
-        // Generate dungeon
-        let mut map = Map::new(MAP_WIDTH, MAP_HEIGHT);
-        map.generate(NUM_ROOMS, MIN_ROOM_SIZE, MAX_ROOM_SIZE);
-
-        // Spawn player in first room
-        let (px, py) = map.player_spawn();
-        let player = Player::new(px, py);
-
-        // Reveal starting room
-        map.reveal_room(0);
-
-        // Spawn enemies in other rooms
-        let spawn_points = map.enemy_spawn_points();
-        let enemies: Vec<Enemy> = spawn_points
-            .into_iter()
-            .map(|(x, y)| Enemy::goblin(x, y))
-            .collect();
-
-        let renderer = Renderer::new();
-
-        Game {
-            map,
-            player,
-            enemies,
+impl Terminal {
+    fn new(game: GameState, renderer: Renderer<TerminalTarget>, playback: Option<Playback>) -> Self {
+        Terminal {
+            game,
             renderer,
+            playback,
             running: true,
         }
     }
 
-    fn run(&mut self) -> std::io::Result<()> {
+    fn run(&mut self) -> io::Result<()> {
         self.renderer.init()?;
 
         while self.running {
-            self.renderer.render(&self.map, &self.player, &self.enemies)?;
+            self.render()?;
 
-            let action = get_input();
+            let Some(action) = self.next_action()? else {
+                self.render()?;
+                self.renderer.render_notice("End of replay. Press any key to exit.")?;
+                wait_for_key();
+                break;
+            };
 
+            let events = self.game.apply(action);
             match action {
-                Action::Quit => {
-                    self.running = false;
-                }
-                Action::Move(dx, dy) => {
-                    self.handle_player_move(dx, dy);
-
-                    if self.player.is_alive() {
-                        self.enemy_turns();
-                    }
-
-                    self.check_game_state()?;
-                }
-                Action::None => {}
+                Action::Quit => self.running = false,
+                // A replay goes on past the point its run was saved and resumed
+                Action::Save if self.playback.is_none() => self.save_and_quit()?,
+                _ => {}
             }
+            self.show_ending(&events)?;
         }
 
         self.renderer.cleanup()?;
         Ok(())
     }
 
-    fn handle_player_move(&mut self, dx: i32, dy: i32) {
-        let new_x = (self.player.x as i32 + dx) as usize;
-        let new_y = (self.player.y as i32 + dy) as usize;
-
-        // Check for enemy at target position
-        if let Some(enemy_idx) = self.enemy_at(new_x, new_y) {
-            let result = player_attack(&self.player, &mut self.enemies[enemy_idx]);
-            self.renderer.add_message(result.message);
-        } else if self.map.is_walkable(new_x, new_y) {
-            self.player.move_by(dx, dy);
-
-            // Reveal the tile the player stepped on
-            self.map.reveal_at(new_x, new_y);
-
-            // If in a corridor, reveal surrounding tiles to see turns
-            if self.map.is_corridor(new_x, new_y) {
-                self.map.reveal_surroundings(new_x, new_y);
-            }
-
-            // Check for potion pickup
-            if self.map.is_potion(new_x, new_y) {
-                let heal_amount = 5;
-                self.player.heal(heal_amount);
-                self.map.pickup_potion(new_x, new_y);
-                self.renderer.add_message(format!("You drink a potion and restore {} HP!", heal_amount));
-            }
+    fn render(&mut self) -> io::Result<()> {
+        self.renderer.render(&self.game)
+    }
 
-            // If player stepped on a door, reveal adjacent rooms
-            if self.map.is_door(new_x, new_y) {
-                // Check all adjacent tiles for rooms (door is in the wall, not in the room)
-                for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
-                    let adj_x = (new_x as i32 + dx) as usize;
-                    let adj_y = (new_y as i32 + dy) as usize;
-                    if let Some(room_idx) = self.map.room_at(adj_x, adj_y) {
-                        self.map.reveal_room(room_idx);
-                    }
-                }
-            }
+    /// The next action from the replay being played back, or else from the keyboard;
+    /// None when the replay has run out
+    fn next_action(&mut self) -> io::Result<Option<Action>> {
+        if let Some(playback) = &mut self.playback {
+            return Ok(playback.next_action());
+        }
 
-            // If player stepped directly into a room (handles doorless entrances)
-            if let Some(room_idx) = self.map.room_at(new_x, new_y) {
-                self.map.reveal_room(room_idx);
-            }
+        match get_input() {
+            Action::Inventory => self.inventory_screen().map(Some),
+            action => Ok(Some(action)),
         }
     }
 
-    fn enemy_turns(&mut self) {
-        let player_x = self.player.x;
-        let player_y = self.player.y;
-
-        for i in 0..self.enemies.len() {
-            if !self.enemies[i].is_alive() {
-                continue;
-            }
-
-            let distance = self.enemies[i].distance_to(player_x, player_y);
-
-            if distance == 1 {
-                // Adjacent to player - attack
-                let result = enemy_attack(&self.enemies[i], &mut self.player);
-                self.renderer.add_message(result.message);
-            } else if distance <= ENEMY_CHASE_RANGE {
-                // Within chase range - move toward player
-                // Create a snapshot of current positions for collision checking
-                let enemies_snapshot: Vec<Enemy> = self.enemies.clone();
-                self.enemies[i].move_toward(player_x, player_y, &self.map, &enemies_snapshot, i, player_x, player_y);
+    /// Show the inventory until the player closes it or picks something to use or drop,
+    /// returning the chosen action
+    fn inventory_screen(&mut self) -> io::Result<Action> {
+        let mut cursor = 0;
+        loop {
+            let player = self.game.player();
+            self.renderer.render_inventory(player, cursor)?;
+            let selected = cursor < player.inventory.len();
+
+            match get_inventory_input() {
+                InventoryAction::Up => cursor = cursor.saturating_sub(1),
+                InventoryAction::Down if cursor + 1 < player.inventory.len() => cursor += 1,
+                InventoryAction::Use if selected => return Ok(Action::UseItem(cursor)),
+                InventoryAction::Drop if selected => return Ok(Action::DropItem(cursor)),
+                InventoryAction::Close => return Ok(Action::None),
+                _ => {}
             }
         }
     }
 
-    fn enemy_at(&self, x: usize, y: usize) -> Option<usize> {
-        self.enemies.iter().position(|e| e.is_alive() && e.x == x && e.y == y)
+    /// Save and stop, or report why the game couldn't be saved and carry on
+    fn save_and_quit(&mut self) -> io::Result<()> {
+        match self.game.save() {
+            Ok(_) => self.running = false,
+            Err(e) => {
+                self.render()?;
+                self.renderer.render_notice(&format!("Could not save: {}", e))?;
+                wait_for_key();
+            }
+        }
+        Ok(())
     }
 
-    fn check_game_state(&mut self) -> std::io::Result<()> {
-        if !self.player.is_alive() {
-            self.renderer.render_game_over()?;
-            wait_for_key();
-            self.running = false;
-        } else if self.all_enemies_dead() {
-            self.renderer.render_victory()?;
+    /// Show the end screen if the game just ended
+    fn show_ending(&mut self, events: &[GameEvent]) -> io::Result<()> {
+        let seed = self.game.config().seed;
+        for event in events {
+            match event {
+                GameEvent::PlayerDied => self.renderer.render_game_over(seed)?,
+                GameEvent::Victory => self.renderer.render_victory(seed)?,
+                _ => continue,
+            }
             wait_for_key();
             self.running = false;
         }
         Ok(())
     }
-
-    fn all_enemies_dead(&self) -> bool {
-        self.enemies.iter().all(|e| !e.is_alive())
-    }
 }
 
 fn main() {
-    let mut game = Game::new();
-
-    if let Err(e) = game.run() {
+    let config = match Config::from_args() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("{}", worldfall::config::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let theme = Theme::load(&config.theme).unwrap_or_else(|e| fail("Error loading theme", e));
+    let renderer = Renderer::new(TerminalTarget, theme, config.charset.glyphs(), config.camera_margin);
+
+    // A saved game or replay brings its own settings
+    let (game, playback) = if let Some(path) = &config.replay {
+        let replay = Replay::read(path).unwrap_or_else(|e| fail("Error loading replay", e));
+        let game = GameState::with_roster(replay.config, replay.roster);
+        (game, Some(Playback::new(replay.actions, config.replay_delay)))
+    } else if config.continue_game {
+        (GameState::load().unwrap_or_else(|e| fail("Error loading saved game", e)), None)
+    } else {
+        (GameState::new(config).unwrap_or_else(|e| fail("Error loading monsters", e)), None)
+    };
+
+    let mut terminal = Terminal::new(game, renderer, playback);
+    if let Err(e) = terminal.run() {
         // Make sure we clean up even on error
-        let _ = game.renderer.cleanup();
+        let _ = terminal.renderer.cleanup();
         eprintln!("Error: {}", e);
     }
+
+    if terminal.playback.is_none() {
+        match terminal.game.replay().write() {
+            Ok(path) => println!("Replay saved to {}", path.display()),
+            Err(e) => eprintln!("Could not save replay: {}", e),
+        }
+    }
+}
+
+fn fail(context: &str, error: String) -> ! {
+    eprintln!("{}: {}", context, error);
+    std::process::exit(1);
 }
@@ -1,37 +1,80 @@
+use std::collections::VecDeque;
+
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::fov::compute_fov;
+use crate::glyph::{Colour, Glyph, GlyphSet};
+
+mod bsp;
+mod cave;
+mod graph;
+
+/// Dungeon layout algorithm used by `Map::generate`
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Generator {
+    /// Rooms dropped at random positions, joined by a minimum spanning tree over
+    /// their centers plus a few extra corridors between nearby rooms for loops
+    Rooms,
+    /// Recursive binary space partition into one leaf per room, with one room in each leaf
+    Bsp,
+    /// Cellular-automata caves with no rooms at all
+    Caves,
+}
 
-// Wall characters indexed by 4-bit mask: UP(1) + DOWN(2) + LEFT(4) + RIGHT(8)
-const WALL_CHARS: [char; 16] = [
-    '#', '│', '│', '│', '─', '┘', '┐', '┤',
-    '─', '└', '┌', '├', '─', '┴', '┬', '┼',
-];
+impl Generator {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rooms" => Some(Generator::Rooms),
+            "bsp" => Some(Generator::Bsp),
+            "caves" => Some(Generator::Caves),
+            _ => None,
+        }
+    }
+}
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Tile {
     Wall,
     Floor,
     Corridor,
     Door,
-    Potion,
+    OpenDoor,
+    StairsDown,
+    StairsUp,
 }
 
 impl Tile {
-    pub fn to_char(&self) -> char {
+    pub fn to_glyph(self, glyphs: &GlyphSet) -> Glyph {
         match self {
-            Tile::Wall => '#',
-            Tile::Floor => '·',
-            Tile::Corridor => ':',
-            Tile::Door => '╬',
-            Tile::Potion => '♥',
+            Tile::Wall => Glyph::new(glyphs.walls[0], Colour::Wall),
+            Tile::Floor => Glyph::new(glyphs.floor, Colour::Floor),
+            Tile::Corridor => Glyph::new(glyphs.corridor, Colour::Corridor),
+            Tile::Door => Glyph::new(glyphs.door, Colour::Door),
+            Tile::OpenDoor => Glyph::new(glyphs.open_door, Colour::Door),
+            Tile::StairsDown => Glyph::new(glyphs.stairs_down, Colour::Stairs),
+            Tile::StairsUp => Glyph::new(glyphs.stairs_up, Colour::Stairs),
         }
     }
 
+    pub fn blocks_sight(&self) -> bool {
+        matches!(self, Tile::Wall | Tile::Door)
+    }
+
     pub fn is_walkable(&self) -> bool {
-        matches!(self, Tile::Floor | Tile::Corridor | Tile::Door | Tile::Potion)
+        matches!(
+            self,
+            Tile::Floor
+                | Tile::Corridor
+                | Tile::Door
+                | Tile::OpenDoor
+                | Tile::StairsDown
+                | Tile::StairsUp
+        )
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Room {
     pub x: usize,
     pub y: usize,
@@ -56,30 +99,68 @@ impl Room {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Map {
     pub width: usize,
     pub height: usize,
     pub tiles: Vec<Vec<Tile>>,
     pub rooms: Vec<Room>,
+    /// Room adjacency graph: `room_links[i]` lists the rooms joined to room `i` by a corridor
+    pub room_links: Vec<Vec<usize>>,
+    /// Where actors may spawn; the first entry is the player's start
+    pub spawn_points: Vec<(usize, usize)>,
+    /// Where items are left lying when the level is populated
+    pub item_spawns: Vec<(usize, usize)>,
+    /// Tiles the player has seen at some point
     pub revealed: Vec<Vec<bool>>,
+    /// Tiles in the player's field of view right now
+    pub visible: Vec<Vec<bool>>,
 }
 
 impl Map {
     pub fn new(width: usize, height: usize) -> Self {
         let tiles = vec![vec![Tile::Wall; width]; height];
         let revealed = vec![vec![false; width]; height];
+        let visible = vec![vec![false; width]; height];
         Map {
             width,
             height,
             tiles,
             rooms: Vec::new(),
+            room_links: Vec::new(),
+            spawn_points: Vec::new(),
+            item_spawns: Vec::new(),
             revealed,
+            visible,
         }
     }
 
-    pub fn generate(&mut self, num_rooms: usize, min_room_size: usize, max_room_size: usize) {
-        let mut rng = rand::thread_rng();
+    /// Generate a level; `loop_ratio` is how many extra corridors to add per
+    /// spanning-tree corridor so room layouts contain cycles
+    pub fn generate<R: Rng>(&mut self, rng: &mut R, generator: Generator, num_rooms: usize, min_room_size: usize, max_room_size: usize, loop_ratio: f64) {
+        match generator {
+            Generator::Rooms => {
+                self.generate_rooms(rng, num_rooms, min_room_size, max_room_size);
+                self.connect_spanning_tree(rng);
+            }
+            Generator::Bsp => self.generate_bsp(rng, num_rooms, min_room_size, max_room_size),
+            Generator::Caves => {
+                // Caves have no rooms, so scatter as many spawns as there would be rooms
+                self.generate_caves(rng, num_rooms);
+                return;
+            }
+        }
+
+        self.room_links.resize(self.rooms.len(), Vec::new());
+        self.add_loops(rng, loop_ratio);
+        self.spawn_points = self.rooms.iter().map(|r| r.center()).collect();
+        self.place_doors();
+        self.place_item_spawns(rng);
+    }
 
+    /// Drop rooms at random positions, rejecting any that overlap an existing room.
+    /// Corridors are added afterwards from the room graph.
+    fn generate_rooms<R: Rng>(&mut self, rng: &mut R, num_rooms: usize, min_room_size: usize, max_room_size: usize) {
         for _ in 0..num_rooms * 10 {
             if self.rooms.len() >= num_rooms {
                 break;
@@ -102,35 +183,91 @@ impl Map {
 
             if !overlaps {
                 self.carve_room(&new_room);
+                self.rooms.push(new_room);
+            }
+        }
+    }
 
-                if !self.rooms.is_empty() {
-                    let (new_x, new_y) = new_room.center();
-                    let (prev_x, prev_y) = self.rooms.last().unwrap().center();
+    /// Join two rooms with an L-shaped corridor between their centers
+    fn connect_rooms<R: Rng>(&mut self, rng: &mut R, from: usize, to: usize) {
+        let (prev_x, prev_y) = self.rooms[from].center();
+        let (new_x, new_y) = self.rooms[to].center();
 
-                    if rng.gen_bool(0.5) {
-                        self.carve_horizontal_corridor(prev_x, new_x, prev_y);
-                        self.carve_vertical_corridor(prev_y, new_y, new_x);
-                    } else {
-                        self.carve_vertical_corridor(prev_y, new_y, prev_x);
-                        self.carve_horizontal_corridor(prev_x, new_x, new_y);
-                    }
-                }
+        if rng.gen_bool(0.5) {
+            self.carve_horizontal_corridor(prev_x, new_x, prev_y);
+            self.carve_vertical_corridor(prev_y, new_y, new_x);
+        } else {
+            self.carve_vertical_corridor(prev_y, new_y, prev_x);
+            self.carve_horizontal_corridor(prev_x, new_x, new_y);
+        }
+    }
 
-                self.rooms.push(new_room);
+    /// Put up stairs on the player's start and down stairs on the floor tile farthest
+    /// from it, inside the room the most corridors away when the level has rooms
+    pub fn place_stairs(&mut self, up: bool, down: bool) {
+        let start = self.player_spawn();
+
+        if down {
+            if let Some((x, y)) = self.farthest_floor() {
+                self.tiles[y][x] = Tile::StairsDown;
             }
         }
 
-        self.place_doors();
-        self.place_potions();
+        if up && self.is_walkable(start.0, start.1) {
+            self.tiles[start.1][start.0] = Tile::StairsUp;
+        }
     }
 
-    /// Place health potions randomly in rooms
-    fn place_potions(&mut self) {
-        let mut rng = rand::thread_rng();
+    /// The free floor tile the longest walk from the player's start, inside the room the
+    /// most corridors away when the level has rooms
+    pub fn farthest_floor(&self) -> Option<(usize, usize)> {
+        let distances = self.walk_distances(self.player_spawn());
+        let target_room = self.farthest_room_from(0);
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.tiles[y][x] == Tile::Floor && !self.spawn_points.contains(&(x, y)))
+            .filter(|pos| !self.item_spawns.contains(pos))
+            .filter(|&(x, y)| target_room.is_none() || self.room_at(x, y) == target_room)
+            .filter(|&(x, y)| distances[y][x] != usize::MAX)
+            .max_by_key(|&(x, y)| distances[y][x])
+    }
 
-        for room in &self.rooms.clone() {
-            // 50% chance to spawn a potion in each room
-            if rng.gen_bool(0.5) {
+    /// Breadth-first walking distance from `start` to every tile (usize::MAX if unreachable)
+    fn walk_distances(&self, start: (usize, usize)) -> Vec<Vec<usize>> {
+        let mut distances = vec![vec![usize::MAX; self.width]; self.height];
+        let mut queue = VecDeque::new();
+        if self.is_walkable(start.0, start.1) {
+            distances[start.1][start.0] = 0;
+            queue.push_back(start);
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let nx = (x as i32 + dx) as usize;
+                let ny = (y as i32 + dy) as usize;
+                if self.is_walkable(nx, ny) && distances[ny][nx] == usize::MAX {
+                    distances[ny][nx] = distances[y][x] + 1;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// Find the first tile of the given kind, scanning row by row
+    pub fn find_tile(&self, tile: Tile) -> Option<(usize, usize)> {
+        self.tiles.iter().enumerate().find_map(|(y, row)| {
+            row.iter().position(|t| *t == tile).map(|x| (x, y))
+        })
+    }
+
+    /// Pick random spots in rooms for items, always rewarding a trip into a dead end
+    fn place_item_spawns<R: Rng>(&mut self, rng: &mut R) {
+        let dead_ends = self.dead_end_rooms();
+        for (room_idx, room) in self.rooms.clone().iter().enumerate() {
+            // 50% chance to spawn an item in each room
+            if dead_ends.contains(&room_idx) || rng.gen_bool(0.5) {
                 // Pick a random floor tile in the room (not center to avoid player/enemy spawn)
                 let x = rng.gen_range(room.x..room.x + room.width);
                 let y = rng.gen_range(room.y..room.y + room.height);
@@ -138,7 +275,7 @@ impl Map {
 
                 // Don't place on room center (spawn point)
                 if (x, y) != (cx, cy) && self.tiles[y][x] == Tile::Floor {
-                    self.tiles[y][x] = Tile::Potion;
+                    self.item_spawns.push((x, y));
                 }
             }
         }
@@ -232,19 +369,18 @@ impl Map {
     }
 
     pub fn is_walkable(&self, x: usize, y: usize) -> bool {
-        self.get_tile(x, y).map_or(false, |t| t.is_walkable())
+        self.get_tile(x, y).is_some_and(|t| t.is_walkable())
     }
 
     pub fn player_spawn(&self) -> (usize, usize) {
-        if let Some(room) = self.rooms.first() {
-            room.center()
-        } else {
-            (self.width / 2, self.height / 2)
-        }
+        self.spawn_points
+            .first()
+            .copied()
+            .unwrap_or((self.width / 2, self.height / 2))
     }
 
     pub fn enemy_spawn_points(&self) -> Vec<(usize, usize)> {
-        self.rooms.iter().skip(1).map(|r| r.center()).collect()
+        self.spawn_points.iter().skip(1).copied().collect()
     }
 
     /// Check if position is a border wall (wall adjacent to non-wall)
@@ -261,7 +397,7 @@ impl Map {
     }
 
     /// Compute the box-drawing character for a wall based on neighboring border walls
-    fn compute_wall_char(&self, x: usize, y: usize) -> char {
+    fn compute_wall_char(&self, x: usize, y: usize, glyphs: &GlyphSet) -> char {
         let ix = x as i32;
         let iy = y as i32;
 
@@ -271,25 +407,20 @@ impl Map {
         if self.is_border_wall_at(ix - 1, iy) { mask |= 4; } // LEFT
         if self.is_border_wall_at(ix + 1, iy) { mask |= 8; } // RIGHT
 
-        WALL_CHARS[mask]
+        glyphs.walls[mask]
     }
 
-    /// Get the display character for a tile, handling wall connections and fog of war
-    pub fn get_tile_char(&self, x: usize, y: usize) -> char {
+    /// Get the glyph for a tile, handling wall connections and fog of war
+    pub fn get_tile_glyph(&self, x: usize, y: usize, glyphs: &GlyphSet) -> Glyph {
         if !self.is_revealed(x, y) {
-            return ' ';
+            return Glyph::BLANK;
         }
         match self.get_tile(x, y) {
-            Some(Tile::Wall) => {
-                // Only render walls that border non-wall tiles
-                if self.is_border_wall(x, y) {
-                    self.compute_wall_char(x, y)
-                } else {
-                    ' '
-                }
-            }
-            Some(tile) => tile.to_char(),
-            None => ' ',
+            // Only render walls that border non-wall tiles
+            Some(Tile::Wall) if self.is_border_wall(x, y) => Glyph::new(self.compute_wall_char(x, y, glyphs), Colour::Wall),
+            Some(Tile::Wall) => Glyph::BLANK,
+            Some(tile) => tile.to_glyph(glyphs),
+            None => Glyph::BLANK,
         }
     }
 
@@ -316,16 +447,43 @@ impl Map {
         false
     }
 
-    /// Check if a tile has been revealed
-    pub fn is_revealed(&self, x: usize, y: usize) -> bool {
-        self.revealed.get(y).and_then(|row| row.get(x)).copied().unwrap_or(false)
+    /// Recompute the tiles currently visible from (x, y) and remember every one of them
+    pub fn update_fov(&mut self, x: usize, y: usize, radius: usize) {
+        let mut visible = std::mem::take(&mut self.visible);
+        for row in visible.iter_mut() {
+            row.fill(false);
+        }
+
+        compute_fov((x, y), radius, |tx, ty| self.blocks_sight(tx, ty), |tx, ty| {
+            if let Some(seen) = visible.get_mut(ty).and_then(|row| row.get_mut(tx)) {
+                *seen = true;
+            }
+        });
+
+        for (revealed_row, visible_row) in self.revealed.iter_mut().zip(&visible) {
+            for (revealed, &seen) in revealed_row.iter_mut().zip(visible_row) {
+                *revealed |= seen;
+            }
+        }
+        self.visible = visible;
     }
 
-    /// Reveal a single tile
-    pub fn reveal_at(&mut self, x: usize, y: usize) {
-        if y < self.height && x < self.width {
-            self.revealed[y][x] = true;
+    /// Walls, closed doors and anything off the map block line of sight
+    fn blocks_sight(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 {
+            return true;
         }
+        self.get_tile(x as usize, y as usize).is_none_or(|t| t.blocks_sight())
+    }
+
+    /// Check if a tile is in the player's current field of view
+    pub fn is_visible(&self, x: usize, y: usize) -> bool {
+        self.visible.get(y).and_then(|row| row.get(x)).copied().unwrap_or(false)
+    }
+
+    /// Check if a tile has been seen at some point (remembered or currently visible)
+    pub fn is_revealed(&self, x: usize, y: usize) -> bool {
+        self.revealed.get(y).and_then(|row| row.get(x)).copied().unwrap_or(false)
     }
 
     /// Find which room contains the given position (returns room index)
@@ -336,80 +494,35 @@ impl Map {
         })
     }
 
-    /// Reveal an entire room including its surrounding walls
-    pub fn reveal_room(&mut self, room_idx: usize) {
-        if room_idx >= self.rooms.len() {
-            return;
-        }
-        let room = self.rooms[room_idx].clone();
-
-        // Reveal the room interior
-        for y in room.y..room.y + room.height {
-            for x in room.x..room.x + room.width {
-                self.revealed[y][x] = true;
-            }
-        }
-
-        // Reveal surrounding walls (1 tile border)
-        let start_x = room.x.saturating_sub(1);
-        let end_x = (room.x + room.width + 1).min(self.width);
-        let start_y = room.y.saturating_sub(1);
-        let end_y = (room.y + room.height + 1).min(self.height);
-
-        // Top and bottom walls
-        for x in start_x..end_x {
-            if start_y < room.y {
-                self.revealed[start_y][x] = true;
-            }
-            if end_y > room.y + room.height && end_y <= self.height {
-                self.revealed[end_y - 1][x] = true;
-            }
-        }
-        // Left and right walls
-        for y in start_y..end_y {
-            if start_x < room.x {
-                self.revealed[y][start_x] = true;
-            }
-            if end_x > room.x + room.width && end_x <= self.width {
-                self.revealed[y][end_x - 1] = true;
-            }
-        }
-    }
-
-    /// Check if position is a door
+    /// Check if position is a closed door
     pub fn is_door(&self, x: usize, y: usize) -> bool {
-        self.get_tile(x, y).map_or(false, |t| *t == Tile::Door)
+        self.get_tile(x, y) == Some(&Tile::Door)
     }
 
-    /// Check if position is a corridor
-    pub fn is_corridor(&self, x: usize, y: usize) -> bool {
-        self.get_tile(x, y).map_or(false, |t| *t == Tile::Corridor)
+    /// Open the closed door at position, if there is one
+    pub fn open_door(&mut self, x: usize, y: usize) {
+        if self.is_door(x, y) {
+            self.tiles[y][x] = Tile::OpenDoor;
+        }
     }
 
-    /// Reveal surrounding tiles (for corridor visibility)
-    pub fn reveal_surroundings(&mut self, x: usize, y: usize) {
-        let ix = x as i32;
-        let iy = y as i32;
-        for dy in -1..=1 {
-            for dx in -1..=1 {
-                let nx = ix + dx;
-                let ny = iy + dy;
-                if nx >= 0 && ny >= 0 && (nx as usize) < self.width && (ny as usize) < self.height {
-                    self.revealed[ny as usize][nx as usize] = true;
-                }
-            }
-        }
+    pub fn is_stairs_down(&self, x: usize, y: usize) -> bool {
+        self.get_tile(x, y) == Some(&Tile::StairsDown)
     }
 
-    /// Check if position has a potion
-    pub fn is_potion(&self, x: usize, y: usize) -> bool {
-        self.get_tile(x, y).map_or(false, |t| *t == Tile::Potion)
+    pub fn is_stairs_up(&self, x: usize, y: usize) -> bool {
+        self.get_tile(x, y) == Some(&Tile::StairsUp)
     }
 
-    /// Pick up potion at position (converts to floor)
-    pub fn pickup_potion(&mut self, x: usize, y: usize) {
-        if y < self.height && x < self.width && self.tiles[y][x] == Tile::Potion {
-            self.tiles[y][x] = Tile::Floor;
+    /// A map drawn as rows of text for tests, where `#` is a wall and anything else floor
+    #[cfg(test)]
+    pub(crate) fn from_rows(rows: &[&str]) -> Self {
+        let mut map = Map::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                map.tiles[y][x] = if c == '#' { Tile::Wall } else { Tile::Floor };
+            }
         }
+        map
     }
 }
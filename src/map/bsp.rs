@@ -0,0 +1,137 @@
+use rand::Rng;
+
+use super::{Map, Room};
+
+/// A rectangular region of the map, including a one-tile wall margin on every side
+#[derive(Clone, Copy)]
+struct Leaf {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Map {
+    /// Split the whole map recursively until there is a leaf for each of
+    /// `num_rooms` rooms, place one room in each leaf and join sibling leaves with corridors
+    pub(super) fn generate_bsp<R: Rng>(&mut self, rng: &mut R, num_rooms: usize, min_room_size: usize, max_room_size: usize) {
+        let root = Leaf {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        };
+        self.split_leaf(rng, root, num_rooms, min_room_size, max_room_size);
+    }
+
+    /// Split `leaf` into about `target` rooms, returning the indices of every room carved inside it
+    fn split_leaf<R: Rng>(&mut self, rng: &mut R, leaf: Leaf, target: usize, min_room_size: usize, max_room_size: usize) -> Vec<usize> {
+        // Smallest leaf that still fits a room plus its walls
+        let min_leaf = min_room_size + 2;
+
+        let can_split_x = leaf.width >= min_leaf * 2;
+        let can_split_y = leaf.height >= min_leaf * 2;
+
+        if target <= 1 || !(can_split_x || can_split_y) {
+            return self.carve_leaf_room(rng, leaf, min_room_size, max_room_size);
+        }
+
+        // Prefer cutting across the longer side so leaves stay roughly square
+        let split_x = if can_split_x && can_split_y {
+            if leaf.width * 4 > leaf.height * 5 {
+                true
+            } else if leaf.height * 4 > leaf.width * 5 {
+                false
+            } else {
+                rng.gen_bool(0.5)
+            }
+        } else {
+            can_split_x
+        };
+
+        let (first, second) = if split_x {
+            let cut = rng.gen_range(min_leaf..=leaf.width - min_leaf);
+            (
+                Leaf { width: cut, ..leaf },
+                Leaf { x: leaf.x + cut, width: leaf.width - cut, ..leaf },
+            )
+        } else {
+            let cut = rng.gen_range(min_leaf..=leaf.height - min_leaf);
+            (
+                Leaf { height: cut, ..leaf },
+                Leaf { y: leaf.y + cut, height: leaf.height - cut, ..leaf },
+            )
+        };
+
+        // Share the rooms out by area so every part of the map gets its fair number
+        let area = |leaf: &Leaf| leaf.width * leaf.height;
+        let first_target = (target * area(&first) + area(&leaf) / 2) / area(&leaf);
+        let first_target = first_target.clamp(1, target - 1);
+
+        let mut rooms = self.split_leaf(rng, first, first_target, min_room_size, max_room_size);
+        let second_rooms = self.split_leaf(rng, second, target - first_target, min_room_size, max_room_size);
+
+        // Join the two halves through their closest pair of rooms
+        let closest = rooms
+            .iter()
+            .flat_map(|&a| second_rooms.iter().map(move |&b| (a, b)))
+            .min_by_key(|&(a, b)| {
+                let (ax, ay) = self.rooms[a].center();
+                let (bx, by) = self.rooms[b].center();
+                ax.abs_diff(bx) + ay.abs_diff(by)
+            });
+        if let Some((a, b)) = closest {
+            self.link_rooms(rng, a, b);
+        }
+
+        rooms.extend(second_rooms);
+        rooms
+    }
+
+    fn carve_leaf_room<R: Rng>(&mut self, rng: &mut R, leaf: Leaf, min_room_size: usize, max_room_size: usize) -> Vec<usize> {
+        if leaf.width < min_room_size + 2 || leaf.height < min_room_size + 2 {
+            return Vec::new();
+        }
+
+        let room_width = rng.gen_range(min_room_size..=max_room_size.min(leaf.width - 2));
+        let room_height = rng.gen_range(min_room_size..=max_room_size.min(leaf.height - 2));
+        let x = rng.gen_range(leaf.x + 1..=leaf.x + leaf.width - 1 - room_width);
+        let y = rng.gen_range(leaf.y + 1..=leaf.y + leaf.height - 1 - room_height);
+
+        let room = Room::new(x, y, room_width, room_height);
+        self.carve_room(&room);
+        self.rooms.push(room);
+        vec![self.rooms.len() - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::map::{Generator, Map};
+
+    /// How many rooms BSP makes on a `width` x `height` map when asked for `num_rooms`
+    fn room_count(seed: u64, width: usize, height: usize, num_rooms: usize) -> usize {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut map = Map::new(width, height);
+        map.generate(&mut rng, Generator::Bsp, num_rooms, 4, 8, 0.0);
+        map.rooms.len()
+    }
+
+    #[test]
+    fn the_smallest_map_gets_more_than_one_room() {
+        for seed in 0..50 {
+            assert_eq!(room_count(seed, 20, 15, 2), 2, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn the_default_map_gets_about_the_rooms_asked_for() {
+        for seed in 0..50 {
+            let rooms = room_count(seed, 100, 35, 12);
+            assert!((10..=12).contains(&rooms), "seed {} made {} rooms", seed, rooms);
+        }
+    }
+}
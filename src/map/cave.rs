@@ -0,0 +1,232 @@
+use rand::Rng;
+
+use super::{Map, Tile};
+
+/// Chance that a tile starts out as wall before smoothing
+const INITIAL_WALL_CHANCE: f64 = 0.45;
+/// Number of cellular-automata smoothing passes
+const SMOOTHING_PASSES: usize = 5;
+/// A tile becomes wall when at least this many of the 3x3 block around it are walls
+const WALL_THRESHOLD: usize = 5;
+/// Reject caves whose largest region covers less than this share of the map
+const MIN_CAVE_FRACTION: f64 = 0.3;
+const MAX_ATTEMPTS: usize = 20;
+/// Minimum Manhattan distance between the player start and any enemy spawn
+const MIN_SPAWN_DISTANCE: usize = 10;
+/// Minimum Manhattan distance between two enemy spawns
+const MIN_SPAWN_SPACING: usize = 4;
+const ITEMS_PER_SPAWN: f64 = 0.5;
+
+impl Map {
+    /// Carve an open cave from random noise, keeping only its largest
+    /// connected region, then scatter `num_spawns` spawn points and some item spots
+    pub(super) fn generate_caves<R: Rng>(&mut self, rng: &mut R, num_spawns: usize) {
+        let mut floor = Vec::new();
+        for _ in 0..MAX_ATTEMPTS {
+            self.fill_noise(rng);
+            for _ in 0..SMOOTHING_PASSES {
+                self.smooth();
+            }
+
+            floor = self.keep_largest_region();
+            if floor.len() as f64 >= (self.width * self.height) as f64 * MIN_CAVE_FRACTION {
+                break;
+            }
+        }
+
+        if floor.is_empty() {
+            return;
+        }
+
+        self.place_cave_spawns(rng, &floor, num_spawns);
+        self.scatter_item_spawns(rng, &floor, (num_spawns as f64 * ITEMS_PER_SPAWN) as usize);
+    }
+
+    fn fill_noise<R: Rng>(&mut self, rng: &mut R) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let border = x == 0 || y == 0 || x == self.width - 1 || y == self.height - 1;
+                self.tiles[y][x] = if border || rng.gen_bool(INITIAL_WALL_CHANCE) {
+                    Tile::Wall
+                } else {
+                    Tile::Floor
+                };
+            }
+        }
+    }
+
+    /// One cellular-automata pass; tiles off the map count as walls
+    fn smooth(&mut self) {
+        let mut next = self.tiles.clone();
+        for (y, row) in next.iter_mut().enumerate().take(self.height - 1).skip(1) {
+            for (x, tile) in row.iter_mut().enumerate().take(self.width - 1).skip(1) {
+                let walls = self.walls_around(x, y);
+                *tile = if walls >= WALL_THRESHOLD { Tile::Wall } else { Tile::Floor };
+            }
+        }
+        self.tiles = next;
+    }
+
+    /// Count walls in the 3x3 block centered on (x, y)
+    fn walls_around(&self, x: usize, y: usize) -> usize {
+        let mut count = 0;
+        for ny in y.saturating_sub(1)..=y + 1 {
+            for nx in x.saturating_sub(1)..=x + 1 {
+                if self.get_tile(nx, ny).is_none_or(|t| *t == Tile::Wall) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Flood fill every floor region, wall off all but the largest and return its tiles
+    fn keep_largest_region(&mut self) -> Vec<(usize, usize)> {
+        let mut region_of = vec![vec![usize::MAX; self.width]; self.height];
+        let mut regions: Vec<Vec<(usize, usize)>> = Vec::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.tiles[y][x] != Tile::Floor || region_of[y][x] != usize::MAX {
+                    continue;
+                }
+
+                let id = regions.len();
+                let mut region = Vec::new();
+                let mut stack = vec![(x, y)];
+                region_of[y][x] = id;
+
+                while let Some((cx, cy)) = stack.pop() {
+                    region.push((cx, cy));
+                    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                        let nx = (cx as i32 + dx) as usize;
+                        let ny = (cy as i32 + dy) as usize;
+                        if self.get_tile(nx, ny) == Some(&Tile::Floor) && region_of[ny][nx] == usize::MAX {
+                            region_of[ny][nx] = id;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+
+                regions.push(region);
+            }
+        }
+
+        let largest = regions
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, region)| region.len())
+            .map(|(id, _)| id);
+
+        for (id, region) in regions.iter().enumerate() {
+            if Some(id) != largest {
+                for &(x, y) in region {
+                    self.tiles[y][x] = Tile::Wall;
+                }
+            }
+        }
+
+        largest.map(|id| regions.swap_remove(id)).unwrap_or_default()
+    }
+
+    /// Pick a player start, then enemy spawns spread away from it and from each other
+    fn place_cave_spawns<R: Rng>(&mut self, rng: &mut R, floor: &[(usize, usize)], num_spawns: usize) {
+        let start = floor[rng.gen_range(0..floor.len())];
+        self.spawn_points = vec![start];
+
+        for _ in 0..num_spawns * 20 {
+            if self.spawn_points.len() >= num_spawns {
+                break;
+            }
+
+            let (x, y) = floor[rng.gen_range(0..floor.len())];
+            let far_from_player = x.abs_diff(start.0) + y.abs_diff(start.1) >= MIN_SPAWN_DISTANCE;
+            let spaced = self.spawn_points[1..]
+                .iter()
+                .all(|&(sx, sy)| x.abs_diff(sx) + y.abs_diff(sy) >= MIN_SPAWN_SPACING);
+
+            if far_from_player && spaced {
+                self.spawn_points.push((x, y));
+            }
+        }
+    }
+
+    fn scatter_item_spawns<R: Rng>(&mut self, rng: &mut R, floor: &[(usize, usize)], count: usize) {
+        for _ in 0..count {
+            let (x, y) = floor[rng.gen_range(0..floor.len())];
+            if !self.spawn_points.contains(&(x, y)) && !self.item_spawns.contains(&(x, y)) {
+                self.item_spawns.push((x, y));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn cave(seed: u64) -> Map {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut map = Map::new(100, 35);
+        map.generate_caves(&mut rng, 12);
+        map
+    }
+
+    fn floor_tiles(map: &Map) -> Vec<(usize, usize)> {
+        (0..map.height)
+            .flat_map(|y| (0..map.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| map.tiles[y][x] == Tile::Floor)
+            .collect()
+    }
+
+    /// How many floor tiles can be walked to from `start`
+    fn reachable_floor(map: &Map, start: (usize, usize)) -> usize {
+        let mut seen = vec![vec![false; map.width]; map.height];
+        let mut stack = vec![start];
+        seen[start.1][start.0] = true;
+        let mut count = 0;
+        while let Some((x, y)) = stack.pop() {
+            count += 1;
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if map.tiles[ny][nx] == Tile::Floor && !seen[ny][nx] {
+                    seen[ny][nx] = true;
+                    stack.push((nx, ny));
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn the_cave_is_one_large_connected_region() {
+        for seed in 0..20 {
+            let map = cave(seed);
+            let floor = floor_tiles(&map);
+            assert!(floor.len() as f64 >= (map.width * map.height) as f64 * MIN_CAVE_FRACTION, "seed {}", seed);
+            assert_eq!(reachable_floor(&map, floor[0]), floor.len(), "seed {} has more than one region", seed);
+        }
+    }
+
+    #[test]
+    fn spawns_are_on_floor_and_spread_out() {
+        for seed in 0..20 {
+            let map = cave(seed);
+            let start = map.player_spawn();
+            assert!(map.spawn_points.len() > 1, "seed {}", seed);
+            for &(x, y) in map.spawn_points.iter().chain(&map.item_spawns) {
+                assert!(map.tiles[y][x] == Tile::Floor, "seed {}", seed);
+            }
+
+            let enemies = map.enemy_spawn_points();
+            for (i, &(x, y)) in enemies.iter().enumerate() {
+                assert!(x.abs_diff(start.0) + y.abs_diff(start.1) >= MIN_SPAWN_DISTANCE, "seed {}", seed);
+                for &(ox, oy) in &enemies[i + 1..] {
+                    assert!(x.abs_diff(ox) + y.abs_diff(oy) >= MIN_SPAWN_SPACING, "seed {}", seed);
+                }
+            }
+        }
+    }
+}
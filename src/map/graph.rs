@@ -0,0 +1,201 @@
+use std::collections::VecDeque;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use super::Map;
+
+/// Only rooms among each other's nearest few are considered for extra loop edges
+const LOOP_CANDIDATE_NEIGHBOURS: usize = 3;
+
+impl Map {
+    /// Manhattan distance between two room centers
+    fn room_distance(&self, a: usize, b: usize) -> usize {
+        let (ax, ay) = self.rooms[a].center();
+        let (bx, by) = self.rooms[b].center();
+        ax.abs_diff(bx) + ay.abs_diff(by)
+    }
+
+    /// Connect every room with a minimum spanning tree over their centers (Prim's algorithm)
+    pub(super) fn connect_spanning_tree<R: Rng>(&mut self, rng: &mut R) {
+        let count = self.rooms.len();
+        if count < 2 {
+            return;
+        }
+
+        let mut in_tree = vec![false; count];
+        // Cheapest known edge into the tree for each room: (distance, tree room)
+        let mut best: Vec<(usize, usize)> = (0..count).map(|i| (self.room_distance(0, i), 0)).collect();
+        in_tree[0] = true;
+
+        for _ in 1..count {
+            let next = (0..count)
+                .filter(|&i| !in_tree[i])
+                .min_by_key(|&i| best[i].0)
+                .expect("rooms left outside the tree");
+            in_tree[next] = true;
+            self.link_rooms(rng, best[next].1, next);
+
+            for i in 0..count {
+                let distance = self.room_distance(next, i);
+                if !in_tree[i] && distance < best[i].0 {
+                    best[i] = (distance, next);
+                }
+            }
+        }
+    }
+
+    /// Add `loop_ratio` extra edges per spanning-tree edge between nearby rooms that
+    /// aren't linked yet, so the level has cycles to circle around
+    pub(super) fn add_loops<R: Rng>(&mut self, rng: &mut R, loop_ratio: f64) {
+        let count = self.rooms.len();
+        let tree_edges = count.saturating_sub(1);
+        let wanted = (tree_edges as f64 * loop_ratio).round() as usize;
+        if wanted == 0 {
+            return;
+        }
+
+        let mut candidates = Vec::new();
+        for a in 0..count {
+            let mut nearest: Vec<usize> = (0..count).filter(|&b| b != a).collect();
+            nearest.sort_by_key(|&b| self.room_distance(a, b));
+            for &b in nearest.iter().take(LOOP_CANDIDATE_NEIGHBOURS) {
+                let edge = (a.min(b), a.max(b));
+                if !self.room_links[a].contains(&b) && !candidates.contains(&edge) {
+                    candidates.push(edge);
+                }
+            }
+        }
+
+        candidates.shuffle(rng);
+        for (a, b) in candidates.into_iter().take(wanted) {
+            self.link_rooms(rng, a, b);
+        }
+    }
+
+    /// Carve a corridor between two rooms and record the edge in the room graph
+    pub(super) fn link_rooms<R: Rng>(&mut self, rng: &mut R, a: usize, b: usize) {
+        if self.room_links.len() < self.rooms.len() {
+            self.room_links.resize(self.rooms.len(), Vec::new());
+        }
+        self.connect_rooms(rng, a, b);
+        self.room_links[a].push(b);
+        self.room_links[b].push(a);
+    }
+
+    /// Rooms linked to exactly one other room
+    pub fn dead_end_rooms(&self) -> Vec<usize> {
+        (0..self.room_links.len())
+            .filter(|&i| self.room_links[i].len() == 1)
+            .collect()
+    }
+
+    /// The room the most corridor hops away from `start` in the room graph
+    pub fn farthest_room_from(&self, start: usize) -> Option<usize> {
+        if start >= self.room_links.len() {
+            return None;
+        }
+
+        let mut hops = vec![usize::MAX; self.room_links.len()];
+        let mut queue = VecDeque::from([start]);
+        hops[start] = 0;
+
+        let mut farthest = start;
+        while let Some(room) = queue.pop_front() {
+            if hops[room] > hops[farthest] {
+                farthest = room;
+            }
+            for &next in &self.room_links[room] {
+                if hops[next] == usize::MAX {
+                    hops[next] = hops[room] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        Some(farthest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::map::Map;
+
+    /// Rooms dropped on the default-sized map and joined by a spanning tree plus loops
+    fn linked_rooms(seed: u64, loop_ratio: f64) -> Map {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut map = Map::new(100, 35);
+        map.generate_rooms(&mut rng, 12, 4, 8);
+        map.connect_spanning_tree(&mut rng);
+        map.room_links.resize(map.rooms.len(), Vec::new());
+        map.add_loops(&mut rng, loop_ratio);
+        map
+    }
+
+    fn edge_count(map: &Map) -> usize {
+        map.room_links.iter().map(Vec::len).sum::<usize>() / 2
+    }
+
+    /// Whether every room can be reached from the first along the links
+    fn is_connected(map: &Map) -> bool {
+        let mut seen = vec![false; map.room_links.len()];
+        let mut stack = vec![0];
+        seen[0] = true;
+        while let Some(room) = stack.pop() {
+            for &next in &map.room_links[room] {
+                if !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        seen.iter().all(|&s| s)
+    }
+
+    #[test]
+    fn without_loops_the_rooms_form_a_spanning_tree() {
+        for seed in 0..20 {
+            let map = linked_rooms(seed, 0.0);
+            assert!(map.rooms.len() > 1);
+            assert_eq!(edge_count(&map), map.rooms.len() - 1, "seed {}", seed);
+            assert!(is_connected(&map), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn loops_add_the_rounded_number_of_new_edges() {
+        for seed in 0..20 {
+            let map = linked_rooms(seed, 0.5);
+            let tree_edges = map.rooms.len() - 1;
+            let extra = (tree_edges as f64 * 0.5).round() as usize;
+            assert_eq!(edge_count(&map), tree_edges + extra, "seed {}", seed);
+            assert!(is_connected(&map));
+
+            for (room, links) in map.room_links.iter().enumerate() {
+                assert!(!links.contains(&room), "room {} is linked to itself", room);
+                for (i, other) in links.iter().enumerate() {
+                    assert!(!links[i + 1..].contains(other), "rooms {} and {} are linked twice", room, other);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_the_farthest_room_and_the_dead_ends() {
+        let mut map = Map::new(10, 10);
+        // 0 - 1 - 2
+        //     |   |
+        //     3 --+
+        //     |
+        //     4
+        map.room_links = vec![vec![1], vec![0, 2, 3], vec![1, 3], vec![1, 2, 4], vec![3]];
+
+        assert_eq!(map.farthest_room_from(0), Some(4));
+        assert_eq!(map.farthest_room_from(4), Some(0));
+        assert_eq!(map.farthest_room_from(5), None);
+        assert_eq!(map.dead_end_rooms(), vec![0, 4]);
+    }
+}
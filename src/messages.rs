@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// How many messages are kept around
+const MAX_MESSAGES: usize = 5;
+
+/// The most recent things that happened, newest last
+#[derive(Default, Serialize, Deserialize)]
+pub struct MessageLog {
+    messages: Vec<String>,
+}
+
+impl MessageLog {
+    pub fn add(&mut self, message: String) {
+        self.messages.push(message);
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+    }
+
+    /// Up to `count` messages, newest first
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &String> {
+        self.messages.iter().rev().take(count)
+    }
+}
@@ -0,0 +1,209 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::map::Map;
+
+/// Extra cost for stepping onto a tile another creature stands on, so paths
+/// route around crowds but still squeeze past when there's no other way
+const OCCUPIED_COST: usize = 10;
+/// Give up after expanding this many tiles, to keep far-off searches cheap
+const MAX_EXPANDED: usize = 4000;
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+/// Cost and back-pointer grids for `find_path`, kept between searches so each one
+/// only resets the tiles the last one touched instead of allocating whole-map grids
+#[derive(Default)]
+pub struct PathScratch {
+    cost: Vec<usize>,
+    came_from: Vec<Option<(usize, usize)>>,
+    /// Indices written since the last reset
+    touched: Vec<usize>,
+}
+
+impl PathScratch {
+    /// Make the grids `size` tiles big with every tile unreached
+    fn reset(&mut self, size: usize) {
+        if self.cost.len() != size {
+            self.cost = vec![usize::MAX; size];
+            self.came_from = vec![None; size];
+        } else {
+            for &index in &self.touched {
+                self.cost[index] = usize::MAX;
+                self.came_from[index] = None;
+            }
+        }
+        self.touched.clear();
+    }
+
+    fn reach(&mut self, index: usize, cost: usize, from: Option<(usize, usize)>) {
+        self.cost[index] = cost;
+        self.came_from[index] = from;
+        self.touched.push(index);
+    }
+}
+
+/// Find the cheapest 4-directional path from `start` to `goal` over walkable tiles
+/// using A*. Tiles for which `is_occupied` returns true are passable but expensive;
+/// the goal itself is never treated as occupied. The returned path excludes `start`
+/// and ends at `goal`. `scratch` can be shared by any number of searches on the same map.
+pub fn find_path<F>(map: &Map, start: (usize, usize), goal: (usize, usize), scratch: &mut PathScratch, is_occupied: F) -> Option<Vec<(usize, usize)>>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    if start == goal {
+        return Some(Vec::new());
+    }
+    if !map.is_walkable(goal.0, goal.1) {
+        return None;
+    }
+
+    let index = |(x, y): (usize, usize)| y * map.width + x;
+    let heuristic = |(x, y): (usize, usize)| x.abs_diff(goal.0) + y.abs_diff(goal.1);
+
+    scratch.reset(map.width * map.height);
+    let mut open = BinaryHeap::new();
+
+    scratch.reach(index(start), 0, None);
+    open.push(Reverse((heuristic(start), 0, start)));
+
+    let mut expanded = 0;
+    while let Some(Reverse((_, g, pos))) = open.pop() {
+        if pos == goal {
+            return Some(rebuild_path(&scratch.came_from, index, start, goal));
+        }
+        // Skip stale queue entries for tiles already reached more cheaply
+        if g > scratch.cost[index(pos)] {
+            continue;
+        }
+
+        expanded += 1;
+        if expanded > MAX_EXPANDED {
+            break;
+        }
+
+        for (dx, dy) in DIRECTIONS {
+            let next = ((pos.0 as i32 + dx) as usize, (pos.1 as i32 + dy) as usize);
+            if !map.is_walkable(next.0, next.1) {
+                continue;
+            }
+
+            let step = if next != goal && is_occupied(next.0, next.1) { 1 + OCCUPIED_COST } else { 1 };
+            let next_cost = g + step;
+            if next_cost < scratch.cost[index(next)] {
+                scratch.reach(index(next), next_cost, Some(pos));
+                open.push(Reverse((next_cost + heuristic(next), next_cost, next)));
+            }
+        }
+    }
+
+    None
+}
+
+fn rebuild_path<I>(came_from: &[Option<(usize, usize)>], index: I, start: (usize, usize), goal: (usize, usize)) -> Vec<(usize, usize)>
+where
+    I: Fn((usize, usize)) -> usize,
+{
+    let mut path = vec![goal];
+    let mut current = goal;
+    while let Some(prev) = came_from[index(current)] {
+        if prev == start {
+            break;
+        }
+        path.push(prev);
+        current = prev;
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_path, PathScratch};
+    use crate::map::Map;
+
+    /// Check that `path` walks one tile at a time over floor from `start` to `goal`
+    fn assert_walks(map: &Map, path: &[(usize, usize)], start: (usize, usize), goal: (usize, usize)) {
+        assert_eq!(path.last(), Some(&goal));
+        assert!(!path.contains(&start));
+        let mut from = start;
+        for &step in path {
+            assert_eq!(from.0.abs_diff(step.0) + from.1.abs_diff(step.1), 1, "{:?} to {:?} is not one step", from, step);
+            assert!(map.is_walkable(step.0, step.1), "{:?} is a wall", step);
+            from = step;
+        }
+    }
+
+    #[test]
+    fn routes_around_an_l_shaped_wall() {
+        let map = Map::from_rows(&[
+            "#######",
+            "#...#.#",
+            "#...#.#",
+            "#.###.#",
+            "#.....#",
+            "#######",
+        ]);
+        let path = find_path(&map, (1, 1), (5, 1), &mut PathScratch::default(), |_, _| false).unwrap();
+        assert_walks(&map, &path, (1, 1), (5, 1));
+        assert_eq!(path.len(), 10);
+    }
+
+    #[test]
+    fn no_path_to_an_unreachable_or_unwalkable_goal() {
+        let map = Map::from_rows(&[
+            "#######",
+            "#..#..#",
+            "#######",
+        ]);
+        assert_eq!(find_path(&map, (1, 1), (4, 1), &mut PathScratch::default(), |_, _| false), None);
+        assert_eq!(find_path(&map, (1, 1), (3, 1), &mut PathScratch::default(), |_, _| false), None);
+    }
+
+    #[test]
+    fn the_path_to_where_you_stand_is_empty() {
+        let map = Map::from_rows(&["#####", "#...#", "#####"]);
+        assert_eq!(find_path(&map, (2, 1), (2, 1), &mut PathScratch::default(), |_, _| false), Some(Vec::new()));
+    }
+
+    #[test]
+    fn occupied_tiles_are_avoided_but_still_passable() {
+        let corridor = Map::from_rows(&["#######", "#.....#", "#######"]);
+        let path = find_path(&corridor, (1, 1), (5, 1), &mut PathScratch::default(), |x, y| (x, y) == (3, 1)).unwrap();
+        assert_eq!(path, vec![(2, 1), (3, 1), (4, 1), (5, 1)], "squeezes past when there's no other way");
+
+        let room = Map::from_rows(&["#######", "#.....#", "#.....#", "#######"]);
+        let path = find_path(&room, (1, 1), (5, 1), &mut PathScratch::default(), |x, y| (x, y) == (3, 1)).unwrap();
+        assert_walks(&room, &path, (1, 1), (5, 1));
+        assert!(!path.contains(&(3, 1)), "goes around when it can");
+        assert_eq!(path.len(), 6);
+    }
+
+    #[test]
+    fn the_goal_never_counts_as_occupied() {
+        let map = Map::from_rows(&["#######", "#.....#", "#.....#", "#######"]);
+        let free = find_path(&map, (1, 1), (5, 1), &mut PathScratch::default(), |_, _| false).unwrap();
+        let taken = find_path(&map, (1, 1), (5, 1), &mut PathScratch::default(), |x, y| (x, y) == (5, 1)).unwrap();
+        assert_eq!(taken.len(), free.len());
+        assert_eq!(taken.last(), Some(&(5, 1)));
+    }
+
+    #[test]
+    fn reused_scratch_gives_the_same_paths_as_fresh_grids() {
+        let map = Map::from_rows(&[
+            "#######",
+            "#...#.#",
+            "#...#.#",
+            "#.###.#",
+            "#.....#",
+            "#######",
+        ]);
+        let mut scratch = PathScratch::default();
+        let searches = [((1, 1), (5, 1)), ((5, 4), (2, 2)), ((1, 1), (4, 1)), ((3, 2), (5, 1))];
+        for (start, goal) in searches {
+            let reused = find_path(&map, start, goal, &mut scratch, |_, _| false);
+            let fresh = find_path(&map, start, goal, &mut PathScratch::default(), |_, _| false);
+            assert_eq!(reused, fresh, "{:?} to {:?}", start, goal);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use worldfall::Action;
+
+use crate::input::{poll_replay_control, ReplayControl};
+
+/// Playback delays never go outside these bounds however often the speed is changed
+const MIN_DELAY: Duration = Duration::from_millis(10);
+const MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// Feeds a recorded run's actions back one at a time
+pub struct Playback {
+    actions: VecDeque<Action>,
+    delay: Duration,
+}
+
+impl Playback {
+    pub fn new(actions: Vec<Action>, delay: Duration) -> Self {
+        Playback {
+            actions: actions.into(),
+            delay: delay.clamp(MIN_DELAY, MAX_DELAY),
+        }
+    }
+
+    /// Wait out the delay, letting the viewer change speed or stop, then hand over
+    /// the next action; None once the replay is over or the viewer stopped it
+    pub fn next_action(&mut self) -> Option<Action> {
+        match poll_replay_control(self.delay) {
+            ReplayControl::Faster => self.delay = (self.delay / 2).max(MIN_DELAY),
+            ReplayControl::Slower => self.delay = (self.delay * 2).min(MAX_DELAY),
+            ReplayControl::Stop => return None,
+            // Redraw straight away rather than after the next action, which keeps its place
+            ReplayControl::Resize => return Some(Action::None),
+            ReplayControl::None => {}
+        }
+        self.actions.pop_front()
+    }
+}
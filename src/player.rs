@@ -1,9 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+use crate::combat::CombatStats;
+use crate::glyph::{Colour, Glyph};
+use crate::item::{Equipment, Item, Slot};
+
+/// How many items the player can carry
+pub const INVENTORY_SLOTS: usize = 10;
+/// Total experience needed to reach level 2, 3, and so on
+const LEVEL_THRESHOLDS: &[u32] = &[10, 25, 45, 70, 100, 140, 185, 235, 290];
+/// Gained with every level
+const HP_PER_LEVEL: i32 = 5;
+const POWER_PER_LEVEL: i32 = 1;
+
+#[derive(Serialize, Deserialize)]
 pub struct Player {
     pub x: usize,
     pub y: usize,
     pub hp: i32,
-    pub max_hp: i32,
+    /// Stats before equipment; `max_hp()` and `combat_stats()` give the totals
+    pub base_max_hp: i32,
     pub power: i32,
+    pub accuracy: i32,
+    pub evasion: i32,
+    pub armor: i32,
+    pub inventory: Vec<Item>,
+    pub equipment: Equipment,
+    pub level: u32,
+    pub xp: u32,
 }
 
 impl Player {
@@ -12,11 +35,60 @@ impl Player {
             x,
             y,
             hp: 20,
-            max_hp: 20,
+            base_max_hp: 20,
             power: 5,
+            accuracy: 10,
+            evasion: 10,
+            armor: 0,
+            inventory: Vec::new(),
+            equipment: Equipment::default(),
+            level: 1,
+            xp: 0,
+        }
+    }
+
+    /// Experience needed for the next level, or None at the top level
+    pub fn next_level_xp(&self) -> Option<u32> {
+        LEVEL_THRESHOLDS.get(self.level as usize - 1).copied()
+    }
+
+    /// Add experience, raising stats for every threshold crossed, and return the levels gained
+    pub fn gain_xp(&mut self, amount: u32) -> u32 {
+        self.xp += amount;
+        let mut gained = 0;
+        while self.next_level_xp().is_some_and(|needed| self.xp >= needed) {
+            self.level += 1;
+            self.base_max_hp += HP_PER_LEVEL;
+            self.power += POWER_PER_LEVEL;
+            self.heal(HP_PER_LEVEL);
+            gained += 1;
+        }
+        gained
+    }
+
+    pub fn max_hp(&self) -> i32 {
+        self.base_max_hp + self.equipment.bonuses().max_hp
+    }
+
+    /// Stats including everything equipped
+    pub fn combat_stats(&self) -> CombatStats {
+        let bonus = self.equipment.bonuses();
+        CombatStats {
+            power: self.power + bonus.power,
+            accuracy: self.accuracy + bonus.accuracy,
+            evasion: self.evasion + bonus.evasion,
+            armor: self.armor + bonus.armor,
         }
     }
 
+    /// Wear an item in its slot, returning whatever was there before
+    pub fn equip(&mut self, item: Item, slot: Slot) -> Option<Item> {
+        let old = self.equipment.slot_mut(slot).replace(item);
+        // Taking off a ring of vitality can leave the player above their new maximum
+        self.hp = self.hp.min(self.max_hp());
+        old
+    }
+
     pub fn move_by(&mut self, dx: i32, dy: i32) {
         self.x = (self.x as i32 + dx) as usize;
         self.y = (self.y as i32 + dy) as usize;
@@ -34,10 +106,19 @@ impl Player {
     }
 
     pub fn heal(&mut self, amount: i32) {
-        self.hp = (self.hp + amount).min(self.max_hp);
+        self.hp = (self.hp + amount).min(self.max_hp());
+    }
+
+    /// Put an item in the pack, handing it back if every slot is taken
+    pub fn add_item(&mut self, item: Item) -> Result<(), Item> {
+        if self.inventory.len() >= INVENTORY_SLOTS {
+            return Err(item);
+        }
+        self.inventory.push(item);
+        Ok(())
     }
 
-    pub fn to_char(&self) -> char {
-        '@'
+    pub fn to_glyph(&self) -> Glyph {
+        Glyph::new('@', Colour::Player)
     }
 }
@@ -1,133 +1,421 @@
-use std::io::{self, Write};
-use crossterm::{
-    cursor::{Hide, MoveTo, Show},
-    execute,
-    style::Print,
-    terminal::{self, Clear, ClearType},
-};
-
-use crate::map::Map;
-use crate::player::Player;
+use std::io;
+
+use crossterm::style::Color;
+
+use crate::camera::Camera;
+use crate::dungeon::Level;
 use crate::enemy::Enemy;
+use crate::frame::{Cell, Frame};
+use crate::game::GameState;
+use crate::glyph::{Colour, Glyph, GlyphSet};
+use crate::item::{Equipment, Item};
+use crate::player::{Player, INVENTORY_SLOTS};
+use crate::target::RenderTarget;
+use crate::theme::Theme;
+
+/// Drawn where an enemy that has gone out of sight was last seen
+const LAST_SEEN_MARKER: Glyph = Glyph::new('?', Colour::Marker);
 
-pub struct Renderer {
-    messages: Vec<String>,
+/// Columns taken by the sidebar
+const SIDEBAR_WIDTH: usize = 30;
+/// With fewer map columns than this beside the sidebar, it folds under the map
+const MIN_MAP_COLUMNS: usize = 40;
+/// Enemies listed when the sidebar is folded under the map
+const FOLDED_ENEMY_ROWS: usize = 3;
+const ENEMY_NAME_WIDTH: usize = 14;
+/// Cells inside the brackets of a health bar
+const BAR_WIDTH: usize = 10;
+const CONTROLS_HINT: &str =
+    "Arrow keys/WASD: move | >/<: stairs | G: pick up | I: inventory | Ctrl+S: save and quit | Q: quit";
+
+/// Lays out the game screen and overlays as frames and shows them on a target
+pub struct Renderer<T: RenderTarget> {
+    target: T,
+    /// What the target is showing right now, if known
+    previous: Option<Frame>,
+    theme: Theme,
+    glyphs: &'static GlyphSet,
+    camera: Camera,
+    /// How close the player can get to the edge of the view before it scrolls
+    camera_margin: usize,
 }
 
-impl Renderer {
-    pub fn new() -> Self {
+impl<T: RenderTarget> Renderer<T> {
+    pub fn new(target: T, theme: Theme, glyphs: &'static GlyphSet, camera_margin: usize) -> Self {
         Renderer {
-            messages: Vec::new(),
+            target,
+            previous: None,
+            theme,
+            glyphs,
+            camera: Camera::default(),
+            camera_margin,
         }
     }
 
-    pub fn init(&self) -> io::Result<()> {
-        terminal::enable_raw_mode()?;
-        execute!(io::stdout(), Hide, Clear(ClearType::All))?;
-        Ok(())
+    pub fn target(&self) -> &T {
+        &self.target
     }
 
-    pub fn cleanup(&self) -> io::Result<()> {
-        terminal::disable_raw_mode()?;
-        execute!(io::stdout(), Show, Clear(ClearType::All))?;
-        Ok(())
+    pub fn target_mut(&mut self) -> &mut T {
+        &mut self.target
     }
 
-    pub fn add_message(&mut self, message: String) {
-        self.messages.push(message);
-        if self.messages.len() > 5 {
-            self.messages.remove(0);
-        }
+    pub fn init(&mut self) -> io::Result<()> {
+        self.previous = None;
+        self.target.init()
     }
 
-    pub fn render(&self, map: &Map, player: &Player, enemies: &[Enemy]) -> io::Result<()> {
-        let mut stdout = io::stdout();
+    pub fn cleanup(&mut self) -> io::Result<()> {
+        self.previous = None;
+        self.target.cleanup()
+    }
+
+    pub fn render(&mut self, game: &GameState) -> io::Result<()> {
+        let (level, player) = (game.level(), game.player());
+        let map = &level.map;
+
+        // Along the bottom: the last three messages, equipment and controls hint
+        let recent: Vec<&String> = game.messages().recent(3).collect();
+        let mut bottom: Vec<String> = (0..3).map(|i| recent.get(i).map_or(String::new(), |m| m.to_string())).collect();
+        bottom.push(equipment_line(player));
+        bottom.push(CONTROLS_HINT.to_string());
 
-        execute!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+        let stats = self.stat_lines(game);
+        let enemies = visible_enemies(level, player);
 
-        // Render map
-        for y in 0..map.height {
-            execute!(stdout, MoveTo(0, y as u16))?;
-            for x in 0..map.width {
-                let ch = self.get_char_at(x, y, map, player, enemies);
-                execute!(stdout, Print(ch))?;
+        let (width, height) = self.target.size()?;
+        let top_height = height.saturating_sub(bottom.len());
+        // Each panel is a column of lines drawn at (x, y)
+        let mut panels: Vec<(usize, usize, Vec<Line>)> = Vec::new();
+        let (view_width, view_height, bottom_y);
+
+        if width > map.width.min(MIN_MAP_COLUMNS) + SIDEBAR_WIDTH {
+            // Sidebar one column to the right of the map, as tall as the space above the bottom lines
+            view_width = (width - SIDEBAR_WIDTH - 1).min(map.width);
+            view_height = top_height.clamp(1, map.height);
+            let rows = top_height.max(view_height);
+            let mut sidebar = stats;
+            sidebar.push(Vec::new());
+            sidebar.push(vec![plain("In view:")]);
+            let enemy_rows = rows.saturating_sub(sidebar.len());
+            sidebar.extend(self.enemy_lines(&enemies, enemy_rows));
+            sidebar.truncate(rows);
+            bottom_y = view_height.max(sidebar.len());
+            panels.push((view_width + 1, 0, sidebar));
+        } else {
+            // Too narrow: fold the sidebar under the map, with the enemies beside the
+            // stats if there is room. Its height stays fixed so the map doesn't jump.
+            let mut in_view = vec![vec![plain("In view:")]];
+            in_view.extend(self.enemy_lines(&enemies, FOLDED_ENEMY_ROWS));
+            let side_by_side = width >= 2 * SIDEBAR_WIDTH;
+            let block = if side_by_side {
+                stats.len().max(1 + FOLDED_ENEMY_ROWS)
+            } else {
+                stats.len() + 1 + FOLDED_ENEMY_ROWS
+            };
+
+            view_width = width.clamp(1, map.width);
+            view_height = top_height.saturating_sub(block).clamp(1, map.height);
+            bottom_y = view_height + block;
+            if side_by_side {
+                panels.push((SIDEBAR_WIDTH, view_height, in_view));
+            } else {
+                panels.push((0, view_height + stats.len(), in_view));
             }
+            panels.push((0, view_height, stats));
         }
 
-        // Render status line
-        let status_y = map.height as u16;
-        execute!(
-            stdout,
-            MoveTo(0, status_y),
-            Print(format!("HP: {}/{}  ", player.hp, player.max_hp))
-        )?;
-
-        // Render messages
-        for (i, message) in self.messages.iter().rev().take(3).enumerate() {
-            execute!(
-                stdout,
-                MoveTo(0, status_y + 1 + i as u16),
-                Print(message)
-            )?;
+        self.camera.follow(
+            (player.x, player.y),
+            (view_width, view_height),
+            (map.width, map.height),
+            self.camera_margin,
+        );
+
+        // Whatever doesn't fit on a very short screen is cut off at the bottom
+        let mut frame = Frame::new(width, height);
+        for y in 0..view_height {
+            for x in 0..view_width {
+                let (map_x, map_y) = (self.camera.x + x, self.camera.y + y);
+                frame.set(x, y, self.get_cell_at(map_x, map_y, level, player));
+            }
         }
 
-        // Render controls hint
-        execute!(
-            stdout,
-            MoveTo(0, status_y + 5),
-            Print("Arrow keys/WASD: move | Q: quit")
-        )?;
+        for (x, y, lines) in &panels {
+            for (i, line) in lines.iter().enumerate() {
+                print_line(&mut frame, *x, y + i, line);
+            }
+        }
+        for (i, line) in bottom.iter().enumerate() {
+            frame.print(0, bottom_y + i, line);
+        }
+
+        self.present(frame)
+    }
 
-        stdout.flush()?;
+    /// The player's level, health, stats and where they are in the run
+    fn stat_lines(&self, game: &GameState) -> Vec<Line> {
+        let player = game.player();
+        let stats = player.combat_stats();
+        let mut health = vec![plain("HP ")];
+        health.extend(self.hp_bar(player.hp, player.max_hp()));
+        health.push(plain(&format!(" {}/{}", player.hp, player.max_hp())));
+
+        vec![
+            vec![plain(&format!("Level {}  XP {}", player.level, xp_progress(player)))],
+            health,
+            vec![plain(&format!("Power {}  Armor {}", stats.power, stats.armor))],
+            vec![plain(&format!("Accuracy {}  Evasion {}", stats.accuracy, stats.evasion))],
+            vec![plain(&format!("Depth {}  Turn {}", game.depth(), game.turns()))],
+            vec![plain(&format!("Seed {}", game.config().seed))],
+        ]
+    }
+
+    /// A line for each enemy, cut down to `rows` lines with the last one saying what was left out
+    fn enemy_lines(&self, enemies: &[&Enemy], rows: usize) -> Vec<Line> {
+        let shown = if enemies.len() > rows { rows.saturating_sub(1) } else { enemies.len() };
+        let mut lines: Vec<Line> = enemies[..shown]
+            .iter()
+            .map(|enemy| {
+                let glyph = enemy.to_glyph();
+                let name: String = enemy.enemy_type.noun.name.chars().take(ENEMY_NAME_WIDTH).collect();
+                let mut line = vec![
+                    Span {
+                        text: glyph.ch.to_string(),
+                        fg: Some(self.theme.colour(glyph.colour)),
+                    },
+                    plain(&format!(" {:<width$} ", name, width = ENEMY_NAME_WIDTH)),
+                ];
+                line.extend(self.hp_bar(enemy.hp, enemy.max_hp));
+                line
+            })
+            .collect();
+
+        let hidden = &enemies[shown..];
+        if rows > 0 && !hidden.is_empty() {
+            let noun = &hidden[0].enemy_type.noun;
+            let summary = if hidden.iter().all(|e| e.enemy_type.noun == *noun) {
+                format!("...and {} more {}", hidden.len(), noun.plural())
+            } else {
+                format!("...and {} more", hidden.len())
+            };
+            lines.push(vec![plain(&summary)]);
+        }
+        lines
+    }
+
+    /// `[#####-----]`, filled in proportion to `hp` and coloured by how much is left
+    fn hp_bar(&self, hp: i32, max_hp: i32) -> Line {
+        let (hp, max_hp) = (hp.max(0) as usize, max_hp.max(1) as usize);
+        // Round up so anything still alive shows at least a sliver
+        let filled = (hp * BAR_WIDTH).div_ceil(max_hp).min(BAR_WIDTH);
+        let colour = if hp * 2 > max_hp {
+            Colour::Green
+        } else if hp * 4 > max_hp {
+            Colour::Yellow
+        } else {
+            Colour::Red
+        };
+        vec![
+            plain("["),
+            Span {
+                text: self.glyphs.bar_full.to_string().repeat(filled),
+                fg: Some(self.theme.colour(colour)),
+            },
+            Span {
+                text: self.glyphs.bar_empty.to_string().repeat(BAR_WIDTH - filled),
+                fg: Some(self.theme.remembered),
+            },
+            plain("]"),
+        ]
+    }
+
+    /// Hand a frame to the target along with the last one, unless its size changed
+    fn present(&mut self, frame: Frame) -> io::Result<()> {
+        let previous = self.previous.take().filter(|p| p.same_size(&frame));
+        self.target.present(&frame, previous.as_ref())?;
+        self.previous = Some(frame);
         Ok(())
     }
 
-    fn get_char_at(&self, x: usize, y: usize, map: &Map, player: &Player, enemies: &[Enemy]) -> char {
+    /// An empty frame the size of the target, for full-screen overlays
+    fn blank_frame(&self) -> io::Result<Frame> {
+        let (width, height) = self.target.size()?;
+        Ok(Frame::new(width, height))
+    }
+
+    /// The glyph at (x, y) in its theme colour, dimmed if only remembered
+    fn get_cell_at(&self, x: usize, y: usize, level: &Level, player: &Player) -> Cell {
+        let glyph = self.get_glyph_at(x, y, level, player);
+        // The last-seen marker is only ever out of sight, and is kept bright to stand out
+        let fg = if level.map.is_visible(x, y) || glyph == LAST_SEEN_MARKER {
+            self.theme.colour(glyph.colour)
+        } else {
+            self.theme.remembered
+        };
+        Cell { ch: glyph.ch, fg: Some(fg) }
+    }
+
+    fn get_glyph_at(&self, x: usize, y: usize, level: &Level, player: &Player) -> Glyph {
+        let (map, enemies) = (&level.map, &level.enemies);
+
         // Check for player
         if player.x == x && player.y == y {
-            return player.to_char();
+            return player.to_glyph();
         }
 
-        // Only show enemies in revealed areas
-        if map.is_revealed(x, y) {
+        // Only show enemies the player can see right now
+        if map.is_visible(x, y) {
             for enemy in enemies {
                 if enemy.is_alive() && enemy.x == x && enemy.y == y {
-                    return enemy.to_char();
+                    return enemy.to_glyph();
                 }
             }
+        } else if enemies.iter().any(|e| e.is_alive() && e.last_seen == Some((x, y))) {
+            // Remembered areas only mark where an enemy was last spotted
+            return LAST_SEEN_MARKER;
+        }
+
+        // Items stay where they were left, so remembered ones are still drawn
+        if map.is_revealed(x, y) {
+            if let Some(i) = level.item_at(x, y) {
+                return level.items[i].item.to_glyph(self.glyphs);
+            }
         }
 
         // Return map tile (handles fog of war internally)
-        map.get_tile_char(x, y)
-    }
-
-    pub fn render_game_over(&self) -> io::Result<()> {
-        let mut stdout = io::stdout();
-        execute!(stdout, Clear(ClearType::All), MoveTo(10, 10))?;
-        execute!(stdout, Print("=== GAME OVER ==="))?;
-        execute!(stdout, MoveTo(10, 12))?;
-        execute!(stdout, Print("You have been slain!"))?;
-        execute!(stdout, MoveTo(10, 14))?;
-        execute!(stdout, Print("Press any key to exit..."))?;
-        stdout.flush()?;
-        Ok(())
+        map.get_tile_glyph(x, y, self.glyphs)
     }
 
-    pub fn render_victory(&self) -> io::Result<()> {
-        let mut stdout = io::stdout();
-        execute!(stdout, Clear(ClearType::All), MoveTo(10, 10))?;
-        execute!(stdout, Print("=== VICTORY! ==="))?;
-        execute!(stdout, MoveTo(10, 12))?;
-        execute!(stdout, Print("All enemies defeated!"))?;
-        execute!(stdout, MoveTo(10, 14))?;
-        execute!(stdout, Print("Press any key to exit..."))?;
-        stdout.flush()?;
-        Ok(())
+    /// Show a one-line notice across the top of the screen, over the map
+    pub fn render_notice(&mut self, text: &str) -> io::Result<()> {
+        let blank = self.blank_frame()?;
+        // Keep the map underneath, unless the screen has been resized since
+        let mut frame = self.previous.clone().filter(|p| p.same_size(&blank)).unwrap_or(blank);
+        frame.clear_row(0);
+        frame.print(0, 0, text);
+        self.present(frame)
+    }
+
+    /// Draw the inventory list over the map with the cursor on the selected item
+    pub fn render_inventory(&mut self, player: &Player, cursor: usize) -> io::Result<()> {
+        let mut frame = self.blank_frame()?;
+        let items = &player.inventory;
+        frame.print(2, 1, &format!("=== INVENTORY ({}/{}) ===", items.len(), INVENTORY_SLOTS));
+
+        if items.is_empty() {
+            frame.print(2, 3, "You are not carrying anything.");
+        }
+        for (i, item) in items.iter().enumerate() {
+            let marker = if i == cursor { '>' } else { ' ' };
+            frame.print(2, 3 + i, &format!("{} {} {}", marker, item.to_glyph(self.glyphs).ch, item.noun().name));
+        }
+
+        let equipped_y = 4 + INVENTORY_SLOTS;
+        frame.print(2, equipped_y, &equipment_line(player));
+        frame.print(2, equipped_y + 2, "Up/Down: select | U/Enter: use or equip | D: drop | I/Esc: close");
+        self.present(frame)
+    }
+
+    pub fn render_game_over(&mut self, seed: u64) -> io::Result<()> {
+        self.render_ending("=== GAME OVER ===", "You have been slain!", seed)
+    }
+
+    pub fn render_victory(&mut self, seed: u64) -> io::Result<()> {
+        self.render_ending("=== VICTORY! ===", "The deepest level is cleared!", seed)
+    }
+
+    fn render_ending(&mut self, title: &str, verdict: &str, seed: u64) -> io::Result<()> {
+        let mut frame = self.blank_frame()?;
+        frame.print(10, 10, title);
+        frame.print(10, 12, verdict);
+        frame.print(10, 13, &format!("Seed: {}", seed));
+        frame.print(10, 14, "Press any key to exit...");
+        self.present(frame)
     }
 }
 
-impl Default for Renderer {
-    fn default() -> Self {
-        Self::new()
+/// A run of text in one colour; `None` is the terminal's own colour
+struct Span {
+    text: String,
+    fg: Option<Color>,
+}
+
+type Line = Vec<Span>;
+
+fn plain(text: &str) -> Span {
+    Span {
+        text: text.to_string(),
+        fg: None,
+    }
+}
+
+fn print_line(frame: &mut Frame, x: usize, y: usize, line: &[Span]) {
+    let mut x = x;
+    for span in line {
+        frame.print_coloured(x, y, &span.text, span.fg);
+        x += span.text.chars().count();
+    }
+}
+
+/// Every living enemy the player can see, nearest first
+fn visible_enemies<'a>(level: &'a Level, player: &Player) -> Vec<&'a Enemy> {
+    let mut in_view: Vec<&Enemy> = level
+        .enemies
+        .iter()
+        .filter(|e| e.is_alive() && level.map.is_visible(e.x, e.y))
+        .collect();
+    in_view.sort_by_key(|e| e.x.abs_diff(player.x).max(e.y.abs_diff(player.y)));
+    in_view
+}
+
+/// Experience so far and the amount needed for the next level
+fn xp_progress(player: &Player) -> String {
+    match player.next_level_xp() {
+        Some(needed) => format!("{}/{}", player.xp, needed),
+        None => player.xp.to_string(),
+    }
+}
+
+/// What the player has equipped in each slot
+fn equipment_line(player: &Player) -> String {
+    let Equipment { weapon, armor, shield, ring } = player.equipment;
+    let name = |item: Option<Item>| item.map_or("-".to_string(), |i| i.noun().name);
+    format!(
+        "Weapon: {}  Armor: {}  Shield: {}  Ring: {}",
+        name(weapon),
+        name(armor),
+        name(shield),
+        name(ring)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enemy::EnemyType;
+    use crate::glyph::ASCII;
+    use crate::target::MemoryTarget;
+
+    fn texts(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|line| line.iter().map(|span| span.text.as_str()).collect()).collect()
+    }
+
+    #[test]
+    fn enemies_left_out_of_the_list_are_summed_up() {
+        let renderer = Renderer::new(MemoryTarget::new(80, 24), Theme::load("dark").unwrap(), &ASCII, 6);
+        let enemy = |name: &str| Enemy::new(0, 0, EnemyType::for_tests(name, &[]));
+        let (imp, goblin, witch) = (enemy("imp"), enemy("goblin"), enemy("witch"));
+
+        let lines = texts(&renderer.enemy_lines(&[&imp, &witch, &witch, &witch], 2));
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("i imp "));
+        assert_eq!(lines[1], "...and 3 more witches");
+
+        let lines = texts(&renderer.enemy_lines(&[&imp, &witch, &goblin], 2));
+        assert_eq!(lines[1], "...and 2 more");
+
+        assert_eq!(renderer.enemy_lines(&[&imp, &witch], 2).len(), 2, "everything fits");
+        assert!(renderer.enemy_lines(&[&imp, &witch], 0).is_empty());
     }
 }
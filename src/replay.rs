@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::action::Action;
+use crate::config::Config;
+use crate::roster::Roster;
+use crate::save::{data_dir, read_versioned, write_versioned};
+
+const REPLAY_MAGIC: &str = "worldfall-replay";
+/// Bump whenever replays change shape or the same actions would play out differently,
+/// so old replays are refused instead of drifting out of step
+const REPLAY_VERSION: u32 = 7;
+
+/// A run's settings, monsters and every action taken in it; replaying the actions
+/// against a game built from the same settings and monsters plays the run out again exactly
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub config: Config,
+    /// Kept with the replay so editing or losing the roster file can't change the run
+    pub roster: Roster,
+    pub actions: Vec<Action>,
+}
+
+impl Replay {
+    pub fn new(config: &Config, roster: &Roster) -> Self {
+        Replay {
+            config: config.clone(),
+            roster: roster.clone(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn read(path: &str) -> Result<Self, String> {
+        read_versioned(Path::new(path), REPLAY_MAGIC, REPLAY_VERSION)
+    }
+
+    /// Write the replay into the data directory, named after the run's seed and the time
+    /// it ended so that replays of the same seed don't overwrite each other
+    pub fn write(&self) -> Result<PathBuf, String> {
+        let dir = data_dir()?.join("replays");
+        let ended = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        let name = format!("{}-{}", self.config.seed, ended);
+
+        // Two runs can end within the same second; number any later ones
+        let mut path = dir.join(format!("{}.replay", name));
+        let mut copy = 1;
+        while path.exists() {
+            copy += 1;
+            path = dir.join(format!("{}-{}.replay", name, copy));
+        }
+        write_versioned(&path, REPLAY_MAGIC, REPLAY_VERSION, self)?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::Path;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::enemy::EnemyType;
+
+/// Where designers keep the monster definitions, relative to the working directory
+pub const DEFAULT_ROSTER_PATH: &str = "data/monsters.toml";
+
+/// Copy of the default roster built into the binary, used when the data file can't be found
+const BUILTIN_ROSTER: &str = include_str!("../data/monsters.toml");
+
+#[derive(Deserialize)]
+struct RosterFile {
+    monster: Vec<EnemyType>,
+}
+
+/// Every kind of monster that can be spawned, as loaded from the data file
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Roster {
+    monsters: Vec<EnemyType>,
+}
+
+impl Roster {
+    /// Load the roster from `path`, or from the default data file (falling back to
+    /// the built-in copy) when no path is given
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let (source, text) = match path {
+            Some(path) => (path, read(path)?),
+            None if Path::new(DEFAULT_ROSTER_PATH).exists() => (DEFAULT_ROSTER_PATH, read(DEFAULT_ROSTER_PATH)?),
+            None => ("built-in roster", BUILTIN_ROSTER.to_string()),
+        };
+        Self::parse(&text).map_err(|e| format!("{}: {}", source, e))
+    }
+
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let file: RosterFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        if file.monster.is_empty() {
+            return Err("needs at least one monster".to_string());
+        }
+
+        for monster in &file.monster {
+            if monster.hp <= 0 {
+                return Err(format!("{} needs positive hp", monster.noun.name));
+            }
+            if monster.min_depth > monster.max_depth {
+                return Err(format!("{} has min_depth above max_depth", monster.noun.name));
+            }
+        }
+
+        Ok(Roster { monsters: file.monster })
+    }
+
+    /// Pick a monster allowed at `depth`, weighted by each monster's spawn weight.
+    /// Unique monsters named in `taken_uniques` already exist and are skipped.
+    pub fn pick<R: Rng>(&self, rng: &mut R, depth: usize, taken_uniques: &[String]) -> Option<&EnemyType> {
+        let allowed: Vec<&EnemyType> = self
+            .monsters
+            .iter()
+            .filter(|m| m.min_depth <= depth && depth <= m.max_depth)
+            .filter(|m| !(m.noun.unique && taken_uniques.contains(&m.noun.name)))
+            .collect();
+        allowed.choose_weighted(rng, |m| m.weight).ok().copied()
+    }
+
+    /// Pick a monster for `depth` like `pick`, or failing that the one allowed deepest,
+    /// so there is always something to fight when the roster runs out of monsters this deep
+    pub fn pick_or_deepest<R: Rng>(&self, rng: &mut R, depth: usize, taken_uniques: &[String]) -> Option<&EnemyType> {
+        self.pick(rng, depth, taken_uniques).or_else(|| {
+            self.monsters
+                .iter()
+                .filter(|m| !(m.noun.unique && taken_uniques.contains(&m.noun.name)))
+                .max_by_key(|m| m.max_depth)
+        })
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    /// A roster entry with the given extra fields after its name
+    fn monster(name: &str, fields: &str) -> String {
+        format!("[[monster]]\nname = \"{}\"\nglyph = \"m\"\npower = 2\nweight = 1\n{}\n", name, fields)
+    }
+
+    fn parse_err(text: &str) -> String {
+        Roster::parse(text).err().expect("roster should be rejected")
+    }
+
+    #[test]
+    fn rejects_monsters_without_hit_points() {
+        let err = parse_err(&monster("wisp", "hp = 0\nmin_depth = 1\nmax_depth = 2"));
+        assert_eq!(err, "wisp needs positive hp");
+    }
+
+    #[test]
+    fn rejects_inverted_depth_ranges() {
+        let err = parse_err(&monster("orc", "hp = 5\nmin_depth = 3\nmax_depth = 2"));
+        assert_eq!(err, "orc has min_depth above max_depth");
+    }
+
+    #[test]
+    fn rejects_missing_fields_and_empty_rosters() {
+        let err = parse_err(&monster("orc", "min_depth = 1\nmax_depth = 2"));
+        assert!(err.contains("missing field `hp`"), "{}", err);
+        assert_eq!(parse_err("monster = []"), "needs at least one monster");
+    }
+
+    #[test]
+    fn picks_only_monsters_allowed_at_the_depth() {
+        let text = monster("kobold", "hp = 4\nmin_depth = 1\nmax_depth = 2") + &monster("ogre", "hp = 20\nmin_depth = 4\nmax_depth = 5");
+        let roster = Roster::parse(&text).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+
+        for _ in 0..50 {
+            assert_eq!(roster.pick(&mut rng, 2, &[]).unwrap().noun.name, "kobold");
+            assert_eq!(roster.pick(&mut rng, 4, &[]).unwrap().noun.name, "ogre");
+        }
+        assert!(roster.pick(&mut rng, 3, &[]).is_none());
+        assert_eq!(roster.pick_or_deepest(&mut rng, 3, &[]).unwrap().noun.name, "ogre");
+    }
+
+    #[test]
+    fn never_picks_a_unique_that_is_already_taken() {
+        let text = monster("Grishnak", "unique = true\nhp = 15\nmin_depth = 1\nmax_depth = 5") + &monster("goblin", "hp = 6\nmin_depth = 1\nmax_depth = 5");
+        let roster = Roster::parse(&text).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let taken = vec!["Grishnak".to_string()];
+
+        let names: Vec<String> = (0..50).map(|_| roster.pick(&mut rng, 1, &[]).unwrap().noun.name.clone()).collect();
+        assert!(names.iter().any(|name| name == "Grishnak"));
+        for _ in 0..50 {
+            assert_eq!(roster.pick(&mut rng, 1, &taken).unwrap().noun.name, "goblin");
+        }
+
+        let only_unique = Roster::parse(&monster("Grishnak", "unique = true\nhp = 15\nmin_depth = 1\nmax_depth = 5")).unwrap();
+        assert!(only_unique.pick(&mut rng, 1, &taken).is_none());
+        assert!(only_unique.pick_or_deepest(&mut rng, 1, &taken).is_none());
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// First word of every save file
+const SAVE_MAGIC: &str = "worldfall-save";
+/// Bump whenever the saved game state changes shape, so old saves are refused cleanly
+const SAVE_VERSION: u32 = 7;
+
+/// Worldfall's folder under the user's data directory
+pub fn data_dir() -> Result<PathBuf, String> {
+    let dir = dirs::data_dir().ok_or("could not find a data directory to save in")?;
+    Ok(dir.join("worldfall"))
+}
+
+/// Where the save file lives
+pub fn save_path() -> Result<PathBuf, String> {
+    Ok(data_dir()?.join("save.json"))
+}
+
+/// Write `state` to the save file
+pub fn write<T: Serialize>(state: &T) -> Result<PathBuf, String> {
+    let path = save_path()?;
+    write_versioned(&path, SAVE_MAGIC, SAVE_VERSION, state)?;
+    Ok(path)
+}
+
+/// Read the save file back
+pub fn read<T: DeserializeOwned>() -> Result<T, String> {
+    read_versioned(&save_path()?, SAVE_MAGIC, SAVE_VERSION)
+}
+
+/// Write `state` as JSON behind a `<magic> <version>` header line
+pub fn write_versioned<T: Serialize>(path: &Path, magic: &str, version: u32, state: &T) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+
+    let body = serde_json::to_string(state).map_err(|e| e.to_string())?;
+    let contents = format!("{} {}\n{}", magic, version, body);
+    fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Read a file written by `write_versioned`, refusing other versions before parsing them
+pub fn read_versioned<T: DeserializeOwned>(path: &Path, magic: &str, expected: u32) -> Result<T, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let (header, body) = contents.split_once('\n').unwrap_or((&contents, ""));
+
+    let version = match header.split_once(' ') {
+        Some((found, version)) if found == magic => version.trim().parse::<u32>().ok(),
+        _ => None,
+    };
+    match version {
+        Some(version) if version == expected => {}
+        Some(version) => {
+            return Err(format!(
+                "{} is version {}, but this build only reads version {}",
+                path.display(),
+                version,
+                expected
+            ))
+        }
+        None => return Err(format!("{} is not a {} file", path.display(), magic)),
+    }
+
+    serde_json::from_str(body).map_err(|e| format!("{}: corrupt file: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAGIC: &str = "worldfall-test";
+
+    /// A file of this test's own in the temp directory
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("worldfall-{}-{}", std::process::id(), name))
+    }
+
+    /// Write `contents` to a temp file, then read it back as version 3
+    fn read_back(name: &str, contents: &str) -> Result<Vec<u32>, String> {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+        let result = read_versioned(&path, MAGIC, 3);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let path = temp_path("round-trip");
+        write_versioned(&path, MAGIC, 3, &vec![1, 2, 3]).unwrap();
+        let read: Result<Vec<u32>, String> = read_versioned(&path, MAGIC, 3);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        let err = read_back("old-version", "worldfall-test 2\n[1, 2, 3]").unwrap_err();
+        assert!(err.ends_with("is version 2, but this build only reads version 3"), "{}", err);
+    }
+
+    #[test]
+    fn refuses_other_kinds_of_file() {
+        let err = read_back("wrong-magic", "worldfall-save 3\n[1, 2, 3]").unwrap_err();
+        assert!(err.ends_with("is not a worldfall-test file"), "{}", err);
+    }
+
+    #[test]
+    fn reports_garbage_after_the_header_as_corrupt() {
+        let err = read_back("garbage", "worldfall-test 3\nnot json at all").unwrap_err();
+        assert!(err.contains(": corrupt file: "), "{}", err);
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor::{Hide, Show},
+    execute, queue,
+    terminal::{self, Clear, ClearType},
+};
+
+use crate::frame::{Cell, Frame};
+
+/// Somewhere finished frames are shown
+pub trait RenderTarget {
+    /// Get ready to draw; nothing is assumed to be on screen afterwards
+    fn init(&mut self) -> io::Result<()>;
+
+    /// Put things back the way they were before `init`
+    fn cleanup(&mut self) -> io::Result<()>;
+
+    /// Width and height in cells, which can change between frames
+    fn size(&self) -> io::Result<(usize, usize)>;
+
+    /// Show `frame`. `previous` is what is showing now, if it is known and the same size.
+    fn present(&mut self, frame: &Frame, previous: Option<&Frame>) -> io::Result<()>;
+}
+
+/// The real terminal, drawn through crossterm on stdout
+pub struct TerminalTarget;
+
+impl RenderTarget for TerminalTarget {
+    fn init(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), Hide, Clear(ClearType::All))
+    }
+
+    fn cleanup(&mut self) -> io::Result<()> {
+        terminal::disable_raw_mode()?;
+        execute!(io::stdout(), Show, Clear(ClearType::All))
+    }
+
+    fn size(&self) -> io::Result<(usize, usize)> {
+        let (width, height) = terminal::size()?;
+        Ok((width as usize, height as usize))
+    }
+
+    /// Send only what changed since the last frame, in one flush. Without a
+    /// previous frame the screen is cleared and drawn in full.
+    fn present(&mut self, frame: &Frame, previous: Option<&Frame>) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        if previous.is_none() {
+            queue!(stdout, Clear(ClearType::All))?;
+        }
+        frame.queue_changes(&mut stdout, previous)?;
+        stdout.flush()
+    }
+}
+
+/// Keeps the last frame in memory instead of drawing it, so what would be on
+/// screen can be inspected
+pub struct MemoryTarget {
+    width: usize,
+    height: usize,
+    screen: Option<Frame>,
+}
+
+impl MemoryTarget {
+    pub fn new(width: usize, height: usize) -> Self {
+        MemoryTarget {
+            width,
+            height,
+            screen: None,
+        }
+    }
+
+    /// Change the size, as a terminal window being resized would
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// The last frame presented, if any
+    pub fn frame(&self) -> Option<&Frame> {
+        self.screen.as_ref()
+    }
+
+    /// The character and colour at (x, y), or None off screen
+    pub fn cell(&self, x: usize, y: usize) -> Option<Cell> {
+        self.screen
+            .as_ref()
+            .filter(|frame| x < frame.width && y < frame.height)
+            .map(|frame| frame.get(x, y))
+    }
+
+    /// The screen as text, one line per row with trailing spaces trimmed
+    pub fn text(&self) -> String {
+        let Some(frame) = &self.screen else {
+            return String::new();
+        };
+        (0..frame.height)
+            .map(|y| {
+                let row: String = (0..frame.width).map(|x| frame.get(x, y).ch).collect();
+                row.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl RenderTarget for MemoryTarget {
+    fn init(&mut self) -> io::Result<()> {
+        self.screen = None;
+        Ok(())
+    }
+
+    fn cleanup(&mut self) -> io::Result<()> {
+        self.screen = None;
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<(usize, usize)> {
+        Ok((self.width, self.height))
+    }
+
+    fn present(&mut self, frame: &Frame, _previous: Option<&Frame>) -> io::Result<()> {
+        self.screen = Some(frame.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crossterm::style::Color;
+use serde::Deserialize;
+
+use crate::glyph::Colour;
+
+/// Themes built into the binary, picked by name with --theme
+const BUILT_IN: &[(&str, &str)] = &[
+    ("dark", include_str!("../data/themes/dark.toml")),
+    ("light", include_str!("../data/themes/light.toml")),
+    ("high-contrast", include_str!("../data/themes/high-contrast.toml")),
+];
+
+#[derive(Deserialize)]
+struct ThemeFile {
+    remembered: String,
+    colours: HashMap<Colour, String>,
+}
+
+/// The terminal colour for every palette entry
+pub struct Theme {
+    colours: HashMap<Colour, Color>,
+    /// Used for everything remembered but out of sight
+    pub remembered: Color,
+}
+
+impl Theme {
+    /// Load a built-in theme by name, or otherwise a theme file at that path
+    pub fn load(name: &str) -> Result<Self, String> {
+        match BUILT_IN.iter().find(|(builtin, _)| *builtin == name) {
+            Some((_, text)) => Theme::parse(text),
+            None => {
+                let text = fs::read_to_string(name)
+                    .map_err(|e| format!("{} is not a built-in theme and could not be read: {}", name, e))?;
+                Theme::parse(&text).map_err(|e| format!("{}: {}", name, e))
+            }
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let file: ThemeFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut colours = HashMap::new();
+        for colour in Colour::ALL {
+            let spec = file
+                .colours
+                .get(&colour)
+                .ok_or_else(|| format!("no colour given for {}", format!("{:?}", colour).to_lowercase()))?;
+            colours.insert(colour, parse_colour(spec)?);
+        }
+        Ok(Theme {
+            colours,
+            remembered: parse_colour(&file.remembered)?,
+        })
+    }
+
+    pub fn colour(&self, colour: Colour) -> Color {
+        self.colours[&colour]
+    }
+}
+
+/// Read "#rrggbb" or one of the terminal's named colours
+fn parse_colour(spec: &str) -> Result<Color, String> {
+    let invalid = || format!("invalid colour: {}", spec);
+    if let Some(hex) = spec.strip_prefix('#') {
+        if hex.len() != 6 {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2).ok_or_else(invalid)?, 16).map_err(|_| invalid());
+        return Ok(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    Color::try_from(spec).map_err(|_| invalid())
+}
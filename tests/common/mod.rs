@@ -0,0 +1,8 @@
+use worldfall::roster::Roster;
+use worldfall::{Config, GameState};
+
+/// A fresh game on the repo's own monster roster, the same every time for a seed
+pub fn new_game(seed: u64) -> GameState {
+    let roster = Roster::load(Some(concat!(env!("CARGO_MANIFEST_DIR"), "/data/monsters.toml"))).unwrap();
+    GameState::with_roster(Config::with_seed(seed), roster)
+}
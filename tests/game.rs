@@ -0,0 +1,158 @@
+mod common;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use worldfall::map::Generator;
+use worldfall::roster::Roster;
+use worldfall::save::{read_versioned, write_versioned};
+use worldfall::{Action, Config, GameEvent, GameState};
+
+use common::new_game;
+
+const SEED: u64 = 7;
+
+/// The first step of the shortest walk to the down stairs, by breadth-first search
+fn step_toward_stairs(game: &GameState) -> Action {
+    let map = &game.level().map;
+    let start = (game.player().x, game.player().y);
+    let mut first_step = vec![vec![None; map.width]; map.height];
+    let mut queue = VecDeque::from([start]);
+    first_step[start.1][start.0] = Some((0, 0));
+
+    while let Some((x, y)) = queue.pop_front() {
+        if map.is_stairs_down(x, y) {
+            let (dx, dy) = first_step[y][x].unwrap();
+            return Action::Move(dx, dy);
+        }
+        for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            let (nx, ny) = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+            if map.is_walkable(nx, ny) && first_step[ny][nx].is_none() {
+                first_step[ny][nx] = if (x, y) == start { Some((dx, dy)) } else { first_step[y][x] };
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    panic!("no way to the down stairs");
+}
+
+/// Walk to the down stairs and take them, returning every action tried
+fn descend(game: &mut GameState) -> Vec<Action> {
+    let mut actions = Vec::new();
+    while !game.level().map.is_stairs_down(game.player().x, game.player().y) {
+        assert!(actions.len() < 1000, "never reached the stairs");
+        let action = step_toward_stairs(game);
+        game.apply(action);
+        assert!(!game.is_over(), "died on the way to the stairs");
+        actions.push(action);
+    }
+    actions
+}
+
+#[test]
+fn actions_that_take_no_turn_report_why() {
+    let mut game = new_game(SEED);
+
+    assert!(game.apply(Action::None).is_empty());
+    let events = game.apply(Action::Descend);
+    assert!(matches!(events.as_slice(), [GameEvent::Message(m)] if m == "There are no stairs down here."));
+    assert_eq!(game.turns(), 0);
+
+    // Doing nothing isn't recorded, a failed attempt at the stairs is
+    assert_eq!(game.replay().actions, vec![Action::Descend]);
+}
+
+#[test]
+fn taking_the_stairs_changes_depth() {
+    let mut game = new_game(SEED);
+    let mut actions = descend(&mut game);
+
+    let events = game.apply(Action::Descend);
+    actions.push(Action::Descend);
+    assert!(events.iter().any(|e| matches!(e, GameEvent::DepthChanged { depth: 2 })));
+    assert!(events.iter().any(|e| matches!(e, GameEvent::Message(m) if m == "You descend to depth 2.")));
+    assert_eq!(game.depth(), 2);
+    assert_eq!(game.replay().actions, actions);
+}
+
+#[test]
+fn replaying_the_recorded_actions_reaches_the_same_state() {
+    let mut game = new_game(SEED);
+    descend(&mut game);
+    game.apply(Action::Descend);
+    for _ in 0..20 {
+        game.apply(Action::Move(1, 0));
+        game.apply(Action::Move(0, 1));
+    }
+
+    let mut replayed = new_game(SEED);
+    for &action in &game.replay().actions {
+        replayed.apply(action);
+    }
+
+    let (a, b) = (game.player(), replayed.player());
+    assert_eq!((a.x, a.y, a.hp, a.xp, a.level), (b.x, b.y, b.hp, b.xp, b.level));
+    assert_eq!(game.depth(), replayed.depth());
+    assert_eq!(game.turns(), replayed.turns());
+    assert_eq!(game.replay().actions, replayed.replay().actions);
+}
+
+#[test]
+fn a_saved_game_plays_on_exactly_like_the_original() {
+    let mut game = new_game(SEED);
+    descend(&mut game);
+    game.apply(Action::Descend);
+
+    let path = std::env::temp_dir().join(format!("worldfall-{}-round-trip.save", std::process::id()));
+    write_versioned(&path, "worldfall-test", 1, &game).unwrap();
+    let loaded: Result<GameState, String> = read_versioned(&path, "worldfall-test", 1);
+    std::fs::remove_file(&path).unwrap();
+    let mut loaded = loaded.unwrap();
+    let saved_at = game.turns();
+
+    // Wander, fight, pick things up and use them; none of the state skipped by the save may matter
+    let choices = [
+        Action::Move(0, -1),
+        Action::Move(0, 1),
+        Action::Move(-1, 0),
+        Action::Move(1, 0),
+        Action::PickUp,
+        Action::UseItem(0),
+        Action::DropItem(1),
+        Action::Descend,
+        Action::Ascend,
+    ];
+    // A walk the player survives, so every action after loading is played out
+    let mut rng = ChaCha8Rng::seed_from_u64(4);
+    for _ in 0..500 {
+        let action = *choices.choose(&mut rng).unwrap();
+        game.apply(action);
+        loaded.apply(action);
+    }
+
+    assert!(game.turns() >= saved_at + 200, "too few turns played after loading");
+    assert_eq!(serde_json::to_string(&game).unwrap(), serde_json::to_string(&loaded).unwrap());
+}
+
+#[test]
+fn enemy_turns_stay_fast_on_the_largest_maps() {
+    let roster = Roster::load(Some(concat!(env!("CARGO_MANIFEST_DIR"), "/data/monsters.toml"))).unwrap();
+    let mut config = Config::with_seed(1);
+    // Caves scatter their spawn points the most, so wanderers head for the most places
+    config.generator = Generator::Caves;
+    (config.map_width, config.map_height) = (400, 200);
+    let mut game = GameState::with_roster(config, roster);
+    assert!(game.level().enemies.len() >= 200);
+
+    // Each turn took most of a second in release builds when every wanderer rebuilt its
+    // own map; now a whole debug-build run of turns fits comfortably in the limit
+    let started = Instant::now();
+    for turn in 0..20 {
+        game.apply(Action::Move(if turn % 2 == 0 { 1 } else { -1 }, 0));
+    }
+    assert_eq!(game.turns(), 20);
+    assert!(started.elapsed() < Duration::from_secs(20), "20 turns took {:?}", started.elapsed());
+}
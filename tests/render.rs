@@ -0,0 +1,90 @@
+mod common;
+
+use worldfall::glyph::ASCII;
+use worldfall::render::Renderer;
+use worldfall::target::MemoryTarget;
+use worldfall::theme::Theme;
+use worldfall::{Action, GameState};
+
+use common::new_game;
+
+const SEED: u64 = 3;
+
+/// Wide and tall enough for the whole 100x35 map with the sidebar beside it
+const WIDE: (usize, usize) = (140, 50);
+
+fn renderer(size: (usize, usize)) -> Renderer<MemoryTarget> {
+    Renderer::new(MemoryTarget::new(size.0, size.1), Theme::load("dark").unwrap(), &ASCII, 6)
+}
+
+fn walk(game: &mut GameState, moves: &[(i32, i32)]) {
+    for &(dx, dy) in moves {
+        game.apply(Action::Move(dx, dy));
+    }
+}
+
+/// The character at (x, y) in a screen snapshot
+fn char_at(text: &str, x: usize, y: usize) -> char {
+    text.lines().nth(y).and_then(|line| line.chars().nth(x)).unwrap_or(' ')
+}
+
+#[test]
+fn fog_hides_unvisited_rooms() {
+    let mut game = new_game(SEED);
+    walk(&mut game, &[(1, 0), (1, 0), (0, 1), (0, 1), (-1, 0)]);
+    let mut renderer = renderer(WIDE);
+    renderer.render(&game).unwrap();
+    let text = renderer.target().text();
+
+    let map = &game.level().map;
+    let unvisited = map
+        .rooms
+        .iter()
+        .find(|r| (r.y..r.y + r.height).all(|y| (r.x..r.x + r.width).all(|x| !map.is_revealed(x, y))))
+        .expect("every room has been seen");
+
+    // The whole map fits, so screen and map coordinates are the same
+    for y in unvisited.y..unvisited.y + unvisited.height {
+        for x in unvisited.x..unvisited.x + unvisited.width {
+            assert_eq!(char_at(&text, x, y), ' ', "unvisited tile drawn at ({}, {})", x, y);
+        }
+    }
+    let player = game.player();
+    assert_eq!(renderer.target().cell(player.x, player.y).unwrap().ch, '@');
+}
+
+#[test]
+fn narrow_screen_folds_the_sidebar_under_the_map() {
+    let game = new_game(SEED);
+    let mut renderer = renderer(WIDE);
+    renderer.render(&game).unwrap();
+    let text = renderer.target().text();
+    let status = text.lines().position(|line| line.contains("Level 1  XP")).unwrap();
+    assert_eq!(status, 0, "sidebar starts at the top right");
+    assert!(!text.lines().next().unwrap().starts_with("Level"));
+
+    // Too narrow for the 30-column sidebar beside 40 columns of map
+    renderer.target_mut().resize(60, 50);
+    renderer.render(&game).unwrap();
+    let text = renderer.target().text();
+    let status = text.lines().position(|line| line.starts_with("Level 1  XP")).unwrap();
+    assert!(status > 0, "stats moved under the map");
+    assert!(text.lines().all(|line| line.chars().count() <= 60));
+    assert!(text.lines().nth(status).unwrap().contains("In view:"), "enemies listed beside the stats");
+}
+
+#[test]
+fn short_screen_never_draws_past_the_bottom() {
+    let game = new_game(SEED);
+    let mut renderer = renderer((140, 8));
+    renderer.render(&game).unwrap();
+    assert_eq!(renderer.target().frame().unwrap().height, 8);
+
+    // Three rows of map are left above the five bottom lines, and the sidebar stops there too
+    let text = renderer.target().text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[..3].iter().any(|line| line.contains('@')), "the camera keeps the player in view");
+    assert!(lines[6].starts_with("Weapon:"));
+    assert!(lines[7].starts_with("Arrow keys"));
+    assert!(lines[3..].iter().all(|line| !line.contains("Level 1") && !line.contains("In view:")));
+}